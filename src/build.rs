@@ -1,24 +1,25 @@
 use crate::{
     database::{Database, Metadata, Mode},
-    dump::TableDumpFiles,
-    parse::compress_redirect_chains,
+    parse::{compress_redirect_chains, TableSource},
 };
 use anyhow::{anyhow, Result};
 use humantime::format_duration;
 use std::{
+    collections::HashMap,
     path::Path,
     sync::{Arc, Mutex},
     time::Instant,
 };
 
 impl Database {
-    /// Build a database in a certain language. Requires the database metadata and the downloaded
-    /// dump files. The database will be built in the specified temporary path and then copied
-    /// to the final path. Note that the temporary path should point to a directory that does not
-    /// yet exist, and the final path fo a file that does not exist.
+    /// Build a database in a certain language. Requires the database metadata and a source of the
+    /// wiki's tables, such as downloaded dump files. The database will be built in the specified
+    /// temporary path and then copied to the final path. Note that the temporary path should point
+    /// to a directory that does not yet exist. An existing file at the final path is only replaced
+    /// once the new database is complete.
     pub fn build(
         metadata: &Metadata,
-        dump_files: &TableDumpFiles,
+        source: &impl TableSource,
         tmp_path: &Path,
         final_path: &Path,
     ) -> Result<()> {
@@ -37,21 +38,27 @@ impl Database {
         let db = Database::open(tmp_path, Mode::Build)?;
 
         {
-            log::info!("parsing page table dump");
-            let title_to_id = dump_files.parse_page_table()?;
-            let title_to_id_len = title_to_id
-                .iter()
-                .map(|(_, namespace_map)| namespace_map.len())
-                .sum::<usize>();
+            log::info!("parsing page table");
+            let title_to_id = source.parse_page_table()?;
+            let title_to_id_len = title_to_id.values().map(HashMap::len).sum::<usize>();
             if title_to_id_len == 0 {
                 return Err(anyhow!(
                     "nothing parsed from page table, possibly caused by schema changes"
                 ));
             }
-            log::info!("{} page titles found!", title_to_id_len);
+            log::info!("{title_to_id_len} page titles found!");
 
-            log::info!("parsing redirect table dump");
-            let mut redirects = dump_files.parse_redirect_table(&title_to_id)?;
+            log::info!("inserting page namespaces into database");
+            let mut txn = db.write_txn()?;
+            for (namespace, namespace_map) in &title_to_id {
+                for id in namespace_map.values() {
+                    db.insert_namespace(&mut txn, *id, *namespace)?;
+                }
+            }
+            txn.commit()?;
+
+            log::info!("parsing redirect table");
+            let mut redirects = source.parse_redirect_table(&title_to_id)?;
             if redirects.is_empty() {
                 return Err(anyhow!(
                     "nothing parsed from redirect table, possibly caused by schema changes"
@@ -70,8 +77,8 @@ impl Database {
             }
             txn.commit()?;
 
-            log::info!("parsing linktarget table dump");
-            let linktarget_to_target = dump_files.parse_linktarget_table(&title_to_id)?;
+            log::info!("parsing linktarget table");
+            let linktarget_to_target = source.parse_linktarget_table(&title_to_id)?;
             if linktarget_to_target.is_empty() {
                 return Err(anyhow!(
                     "nothing parsed from linktarget table, possibly caused by schema changes"
@@ -81,9 +88,9 @@ impl Database {
 
             drop(title_to_id); // not needed anymore
 
-            log::info!("parsing pagelinks table dump & inserting links into database");
+            log::info!("parsing pagelinks table & inserting links into database");
             let link_count = Arc::new(Mutex::new(0));
-            dump_files.parse_pagelinks_table(&redirects, &linktarget_to_target, |batch| {
+            source.parse_pagelinks_table(&redirects, &linktarget_to_target, |batch| {
                 let mut txn = db.write_txn()?;
                 let size = batch.size();
 
@@ -121,6 +128,11 @@ impl Database {
         db.generate_outgoing_table(&mut txn)?;
         txn.commit()?;
 
+        log::info!("removing namespaces of unlinked pages");
+        let mut txn = db.write_txn()?;
+        db.prune_namespaces(&mut txn)?;
+        txn.commit()?;
+
         log::info!("copying database to final path");
         db.copy_to_serve(final_path)?;
 
@@ -1,23 +1,25 @@
 use anyhow::{anyhow, Context, Result};
-use heed::types::SerdeBincode;
+use heed::types::{DecodeIgnore, SerdeBincode, Str};
 use heed::{EnvFlags, EnvOpenOptions, PutFlags, RoTxn};
 use regex::Regex;
 use serde::Serialize;
-use std::collections::BTreeMap;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashSet};
 use std::fs;
 use std::path::Path;
 
-/// See https://www.mediawiki.org/wiki/Manual:Pagelinks_table
+/// See <https://www.mediawiki.org/wiki/Manual:Pagelinks_table>
 pub type PageId = u32;
 
-/// See https://www.mediawiki.org/wiki/Manual:Pagelinks_table
+/// See <https://www.mediawiki.org/wiki/Manual:Pagelinks_table>
 pub type PageNamespaceId = i32;
 
-/// See https://www.mediawiki.org/wiki/Manual:Linktarget_table
+/// See <https://www.mediawiki.org/wiki/Manual:Linktarget_table>
 pub type LinkTargetId = u64;
 
 /// A struct containing metadata about a database. The language code represents
-/// the Wikipedia language, and the date code represents the dump date.
+/// the Wikipedia language (or a user-chosen name for other wikis), and the date code
+/// represents the dump date.
 #[derive(Debug, Serialize, Clone, PartialEq, Eq, Hash)]
 #[serde(rename_all = "camelCase")]
 pub struct Metadata {
@@ -26,9 +28,16 @@ pub struct Metadata {
 }
 
 impl Metadata {
+    /// Create metadata from a language code (or wiki name) and date code. Returns an error if
+    /// they cannot be used in a database name.
+    pub fn new(language_code: &str, date_code: &str) -> Result<Self> {
+        Self::from_name(&format!("wp-{language_code}-{date_code}"))
+            .context("name may only contain letters, digits and underscores, and date only digits")
+    }
+
     /// Extract metadata from the name of a database.
     fn from_name(s: &str) -> Result<Self> {
-        let re = Regex::new(r"^wp-([a-zA-Z]+)-([0-9]+)$")?;
+        let re = Regex::new(r"^wp-([a-zA-Z0-9_]+)-([0-9]+)$")?;
         if let Some(caps) = re.captures(s) {
             if let Some(language_code) = caps.get(1) {
                 if let Some(date_code) = caps.get(2) {
@@ -46,14 +55,6 @@ impl Metadata {
     pub fn to_name(&self) -> String {
         format!("wp-{}-{}", self.language_code, self.date_code)
     }
-
-    pub fn is_newer(&self, other: &Self) -> bool {
-        self.language_code == other.language_code && self.date_code > other.date_code
-    }
-
-    pub fn is_older(&self, other: &Self) -> bool {
-        self.language_code == other.language_code && self.date_code < other.date_code
-    }
 }
 
 /// The modes in which a database can be opened.
@@ -67,7 +68,7 @@ pub enum Mode {
 pub struct Database {
     pub metadata: Metadata,
     mode: Mode,
-    env: heed::Env<heed::WithTls>,
+    env: heed::Env<heed::WithoutTls>,
     tables: Tables,
 }
 
@@ -76,6 +77,82 @@ struct Tables {
     redirects: heed::Database<SerdeBincode<PageId>, SerdeBincode<PageId>>,
     incoming: heed::Database<SerdeBincode<PageId>, SerdeBincode<Vec<PageId>>>,
     outgoing: heed::Database<SerdeBincode<PageId>, SerdeBincode<Vec<PageId>>>,
+    titles: Option<heed::Database<SerdeBincode<PageId>, Str>>, // absent in older databases
+    namespaces: Option<heed::Database<SerdeBincode<PageId>, SerdeBincode<PageNamespaceId>>>, // absent in older databases
+}
+
+/// Version of the database format, stored in the meta table. Databases built before the meta table
+/// was introduced are considered version 1. Increment when changing the format in a way older
+/// versions of Wikipath cannot read.
+pub const FORMAT_VERSION: u32 = 2;
+
+const FORMAT_VERSION_KEY: &str = "format_version";
+
+/// The number of entries in each table of a database. Optional tables are `None` if absent.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableLengths {
+    pub redirects: u64,
+    pub incoming: u64,
+    pub outgoing: u64,
+    pub titles: Option<u64>,
+    pub namespaces: Option<u64>,
+}
+
+/// Compare two page ids in the order in which they are stored as keys in the tables. Keys are
+/// bincode-encoded in little-endian, so this order is not numerical. Useful when merging the
+/// iterators of multiple tables.
+pub fn cmp_keys(a: PageId, b: PageId) -> Ordering {
+    a.to_le_bytes().cmp(&b.to_le_bytes())
+}
+
+/// Merge two iterators over table entries that are both in key order (see `cmp_keys`) into one
+/// iterator in key order, pairing up the values of keys present in both.
+pub fn merge_by_key<A, B>(
+    a: impl Iterator<Item = Result<(PageId, A)>>,
+    b: impl Iterator<Item = Result<(PageId, B)>>,
+) -> impl Iterator<Item = Result<(PageId, Option<A>, Option<B>)>> {
+    let mut a = a.fuse();
+    let mut b = b.fuse();
+    let mut next_a = None;
+    let mut next_b = None;
+
+    std::iter::from_fn(move || {
+        if next_a.is_none() {
+            next_a = match a.next().transpose() {
+                Ok(entry) => entry,
+                Err(e) => return Some(Err(e)),
+            };
+        }
+        if next_b.is_none() {
+            next_b = match b.next().transpose() {
+                Ok(entry) => entry,
+                Err(e) => return Some(Err(e)),
+            };
+        }
+
+        let order = match (&next_a, &next_b) {
+            (None, None) => return None,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some((key_a, _)), Some((key_b, _))) => cmp_keys(*key_a, *key_b),
+        };
+        Some(Ok(match order {
+            Ordering::Less => {
+                let (key, value) = next_a.take()?;
+                (key, Some(value), None)
+            }
+            Ordering::Greater => {
+                let (key, value) = next_b.take()?;
+                (key, None, Some(value))
+            }
+            Ordering::Equal => {
+                let (key, value_a) = next_a.take()?;
+                let (_, value_b) = next_b.take()?;
+                (key, Some(value_a), Some(value_b))
+            }
+        }))
+    })
 }
 
 impl Database {
@@ -105,7 +182,8 @@ impl Database {
 
         let env = unsafe {
             EnvOpenOptions::new()
-                .max_dbs(3) // redirects, incoming, outgoing
+                .read_txn_without_tls() // searches may open read transactions on threads holding one
+                .max_dbs(6) // redirects, incoming, outgoing, titles, namespaces, meta
                 .map_size(32 * 1024 * 1024 * 1024) // max total database size
                 .flags(match mode {
                     Mode::Serve => EnvFlags::NO_SUB_DIR | EnvFlags::READ_ONLY,
@@ -120,15 +198,36 @@ impl Database {
                 let redirects = env.create_database(&mut txn, Some("redirects"))?;
                 let incoming = env.create_database(&mut txn, Some("incoming"))?;
                 let outgoing = env.create_database(&mut txn, Some("outgoing"))?;
+                let titles = env.create_database(&mut txn, Some("titles"))?;
+                let namespaces = env.create_database(&mut txn, Some("namespaces"))?;
+                let meta: heed::Database<Str, SerdeBincode<u32>> =
+                    env.create_database(&mut txn, Some("meta"))?;
+                meta.put(&mut txn, FORMAT_VERSION_KEY, &FORMAT_VERSION)?;
                 txn.commit()?;
                 Tables {
                     redirects,
                     incoming,
                     outgoing,
+                    titles: Some(titles),
+                    namespaces: Some(namespaces),
                 }
             }
             Mode::Serve => {
                 let txn = env.read_txn()?;
+                let meta: Option<heed::Database<Str, SerdeBincode<u32>>> =
+                    env.open_database(&txn, Some("meta"))?;
+                let format_version = match meta {
+                    Some(meta) => meta
+                        .get(&txn, FORMAT_VERSION_KEY)?
+                        .context("serve database is missing format version")?,
+                    None => 1,
+                };
+                if format_version > FORMAT_VERSION {
+                    return Err(anyhow!(
+                        "serve database has format version {format_version}, but only versions up to {FORMAT_VERSION} are supported"
+                    ));
+                }
+
                 let redirects = env
                     .open_database(&txn, Some("redirects"))?
                     .context("serve database is missing redirects table")?;
@@ -138,11 +237,19 @@ impl Database {
                 let outgoing = env
                     .open_database(&txn, Some("outgoing"))?
                     .context("serve database is missing outgoing table")?;
+                let titles = env.open_database(&txn, Some("titles"))?;
+                let namespaces = env.open_database(&txn, Some("namespaces"))?;
+                let namespaces = match namespaces {
+                    Some(table) if !table.is_empty(&txn)? => Some(table),
+                    _ => None, // imported databases have an empty table
+                };
                 txn.commit()?;
                 Tables {
                     redirects,
                     incoming,
                     outgoing,
+                    titles,
+                    namespaces,
                 }
             }
         };
@@ -167,7 +274,7 @@ impl Database {
     }
 
     /// Create a read transaction on the database. Do not forget to commit the transaction.
-    pub fn read_txn(&self) -> Result<heed::RoTxn<'_, heed::WithTls>> {
+    pub fn read_txn(&self) -> Result<heed::RoTxn<'_, heed::WithoutTls>> {
         Ok(self.env.read_txn()?)
     }
 
@@ -195,6 +302,22 @@ impl Database {
             .unwrap_or(Vec::new()))
     }
 
+    /// Whether a page has any incoming or outgoing links, without decoding them.
+    pub fn has_links(&self, txn: &RoTxn<'_>, page: PageId) -> Result<bool> {
+        Ok(self
+            .tables
+            .incoming
+            .remap_data_type::<DecodeIgnore>()
+            .get(txn, &page)?
+            .is_some()
+            || self
+                .tables
+                .outgoing
+                .remap_data_type::<DecodeIgnore>()
+                .get(txn, &page)?
+                .is_some())
+    }
+
     /// Get the outgoing links of a page.
     pub fn get_outgoing_links(&self, txn: &RoTxn<'_>, source: PageId) -> Result<Vec<PageId>> {
         Ok(self
@@ -204,6 +327,140 @@ impl Database {
             .unwrap_or(Vec::new()))
     }
 
+    /// Get the title of a page, if the database contains titles.
+    pub fn get_title(&self, txn: &RoTxn<'_>, page: PageId) -> Result<Option<String>> {
+        match &self.tables.titles {
+            Some(titles) => Ok(titles.get(txn, &page)?.map(str::to_string)),
+            None => Ok(None),
+        }
+    }
+
+    /// Insert the title of a page into the database, overwriting any previous title.
+    pub fn insert_title(&self, txn: &mut heed::RwTxn<'_>, page: PageId, title: &str) -> Result<()> {
+        let titles = self
+            .tables
+            .titles
+            .as_ref()
+            .context("database has no titles table")?;
+        titles.put(txn, &page, title)?;
+        Ok(())
+    }
+
+    /// Get the namespace of a page. Returns an error if the database does not contain namespaces,
+    /// which is the case for older and imported databases.
+    pub fn get_namespace(&self, txn: &RoTxn<'_>, page: PageId) -> Result<Option<PageNamespaceId>> {
+        let namespaces = self
+            .tables
+            .namespaces
+            .as_ref()
+            .context("database does not contain namespaces")?;
+        Ok(namespaces.get(txn, &page)?)
+    }
+
+    /// Insert the namespace of a page into the database, overwriting any previous namespace.
+    pub fn insert_namespace(
+        &self,
+        txn: &mut heed::RwTxn<'_>,
+        page: PageId,
+        namespace: PageNamespaceId,
+    ) -> Result<()> {
+        let namespaces = self
+            .tables
+            .namespaces
+            .as_ref()
+            .context("database has no namespaces table")?;
+        namespaces.put(txn, &page, &namespace)?;
+        Ok(())
+    }
+
+    /// Remove the namespaces of pages that have no links and are neither the source nor the target
+    /// of a redirect, since they are not part of the graph.
+    pub fn prune_namespaces(&self, txn: &mut heed::RwTxn<'_>) -> Result<()> {
+        let Some(namespaces) = &self.tables.namespaces else {
+            return Ok(());
+        };
+
+        let mut redirect_targets = HashSet::new();
+        for entry in self.tables.redirects.iter(txn)? {
+            let (_, target) = entry?;
+            redirect_targets.insert(target);
+        }
+
+        let redirects = self.tables.redirects.remap_data_type::<DecodeIgnore>();
+        let mut unlinked = Vec::new();
+        for entry in namespaces.remap_data_type::<DecodeIgnore>().iter(txn)? {
+            let (page, ()) = entry?;
+            if !self.has_links(txn, page)?
+                && redirects.get(txn, &page)?.is_none()
+                && !redirect_targets.contains(&page)
+            {
+                unlinked.push(page);
+            }
+        }
+
+        log::debug!("removing namespaces of {} unlinked pages", unlinked.len());
+        for page in unlinked {
+            namespaces.delete(txn, &page)?;
+        }
+        Ok(())
+    }
+
+    /// Get the number of entries in each table.
+    pub fn table_lengths(&self, txn: &RoTxn<'_>) -> Result<TableLengths> {
+        Ok(TableLengths {
+            redirects: self.tables.redirects.len(txn)?,
+            incoming: self.tables.incoming.len(txn)?,
+            outgoing: self.tables.outgoing.len(txn)?,
+            titles: self.tables.titles.map(|t| t.len(txn)).transpose()?,
+            namespaces: self.tables.namespaces.map(|t| t.len(txn)).transpose()?,
+        })
+    }
+
+    /// Iterate over all redirects as (source, target) pairs, in key order (see `cmp_keys`).
+    pub fn iter_redirects<'t>(
+        &self,
+        txn: &'t RoTxn<'_>,
+    ) -> Result<impl Iterator<Item = Result<(PageId, PageId)>> + 't> {
+        Ok(self.tables.redirects.iter(txn)?.map(|entry| Ok(entry?)))
+    }
+
+    /// Iterate over all pages with incoming links as (target, sources) pairs, in key order (see
+    /// `cmp_keys`).
+    pub fn iter_incoming<'t>(
+        &self,
+        txn: &'t RoTxn<'_>,
+    ) -> Result<impl Iterator<Item = Result<(PageId, Vec<PageId>)>> + 't> {
+        Ok(self.tables.incoming.iter(txn)?.map(|entry| Ok(entry?)))
+    }
+
+    /// Iterate over all pages with outgoing links as (source, targets) pairs, in key order (see
+    /// `cmp_keys`).
+    pub fn iter_outgoing<'t>(
+        &self,
+        txn: &'t RoTxn<'_>,
+    ) -> Result<impl Iterator<Item = Result<(PageId, Vec<PageId>)>> + 't> {
+        Ok(self.tables.outgoing.iter(txn)?.map(|entry| Ok(entry?)))
+    }
+
+    /// Iterate over all pages with incoming or outgoing links as (page, in-degree, out-degree)
+    /// triples, in key order (see `cmp_keys`). Merges the iterators of both tables, so memory use
+    /// is bounded.
+    pub fn iter_pages<'t>(
+        &self,
+        txn: &'t RoTxn<'_>,
+    ) -> Result<impl Iterator<Item = Result<(PageId, usize, usize)>> + 't> {
+        Ok(
+            merge_by_key(self.iter_incoming(txn)?, self.iter_outgoing(txn)?).map(|entry| {
+                let (page, sources, targets) = entry?;
+                Ok((
+                    page,
+                    sources.map_or(0, |sources| sources.len()),
+                    targets.map_or(0, |targets| targets.len()),
+                ))
+            }),
+        )
+    }
+
     /// Insert a redirect into the database. Returns an error if the source page already has
     /// a redirect.
     pub fn insert_redirect(
@@ -268,24 +525,39 @@ impl Database {
     }
 
     /// Finish the database by copying it to a file, converting it to a serve database. The database
-    /// is compacted in the process. Only works if the current database is a build database. The build
-    /// database directory is removed at the end.
+    /// is compacted in the process, and atomically moved into place once it has been written, replacing
+    /// any existing file at the path. Only works if the current database is a build database. The
+    /// build database directory is removed at the end.
     pub fn copy_to_serve(self, path: &Path) -> Result<()> {
         if self.mode != Mode::Build {
             return Err(anyhow!("copying to serve is only allowed in build mode"));
         }
 
-        if path.exists() {
-            return Err(anyhow!(
-                "serve database path '{}' already exists",
-                path.display()
-            ));
+        // Copy to a temporary file in the same directory first and move it into place once it is
+        // complete, so that a serving process watching the directory never sees a partial file.
+        let file_name = path
+            .file_name()
+            .and_then(|s| s.to_str())
+            .context("serve database path has no valid filename")?;
+        let partial_path = path.with_file_name(format!(".{file_name}.partial"));
+        if partial_path.exists() {
+            log::warn!("partial database from previous build found, removing");
+            fs::remove_file(&partial_path)?;
         }
 
-        log::debug!("copying database to file");
-        let mut file = fs::File::create(path)?;
+        log::debug!("copying database to temporary file");
+        let mut file = fs::File::create(&partial_path)?;
         self.env
             .copy_to_file(&mut file, heed::CompactionOption::Enabled)?;
+        file.sync_all()?;
+        drop(file);
+
+        log::debug!("moving database file into place");
+        fs::rename(&partial_path, path)?;
+        #[cfg(unix)]
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::File::open(dir)?.sync_all()?; // persist the rename
+        }
 
         log::debug!("removing build database directory");
         let build_path = self.env.path().to_path_buf();
@@ -295,3 +567,37 @@ impl Database {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prunes_namespaces_of_pages_outside_the_graph() {
+        let dir = std::env::temp_dir().join(format!("wikipath-database-{}", std::process::id()));
+        let db_path = dir.join("wp-namespaces-20260101");
+        fs::create_dir_all(&db_path).unwrap();
+
+        // Page 1 links to page 2, page 3 redirects to page 4, and page 5 is unlinked.
+        let db = Database::open(&db_path, Mode::Build).unwrap();
+        let mut txn = db.write_txn().unwrap();
+        for page in 1..=5 {
+            db.insert_namespace(&mut txn, page, 0).unwrap();
+        }
+        db.insert_links_incoming(&mut txn, 2, vec![1]).unwrap();
+        db.insert_redirect(&mut txn, 3, 4).unwrap();
+        db.generate_outgoing_table(&mut txn).unwrap();
+        db.prune_namespaces(&mut txn).unwrap();
+        txn.commit().unwrap();
+
+        let txn = db.read_txn().unwrap();
+        for page in 1..=4 {
+            assert_eq!(db.get_namespace(&txn, page).unwrap(), Some(0));
+        }
+        assert_eq!(db.get_namespace(&txn, 5).unwrap(), None);
+        txn.commit().unwrap();
+        drop(db);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,276 @@
+use crate::{
+    database::{merge_by_key, Database, Metadata, PageId},
+    misc::TopPages,
+};
+use anyhow::Result;
+use heed::RoTxn;
+use serde::Serialize;
+use std::{cmp::Ordering, fmt};
+
+/// The differences between an old and a new database, typically of the same language but with
+/// different dump dates.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DatabaseDiff {
+    old: Metadata,
+    new: Metadata,
+    pages: Changes,
+    links: Changes,
+    redirects: Changes,
+    redirects_retargeted: u64,
+    most_changed_links: Vec<PageLinkChanges>,
+    most_changed_in_degree: Vec<PageDegreeChange>,
+}
+
+/// The number of entries of some kind in both databases, and how many were added and removed.
+#[derive(Debug, Default, Serialize)]
+pub struct Changes {
+    old: u64,
+    new: u64,
+    added: u64,
+    removed: u64,
+}
+
+impl Changes {
+    fn add(&mut self, in_old: bool, in_new: bool) {
+        self.old += u64::from(in_old);
+        self.new += u64::from(in_new);
+        self.added += u64::from(!in_old && in_new);
+        self.removed += u64::from(in_old && !in_new);
+    }
+}
+
+/// The number of outgoing links of a page that were added and removed.
+#[derive(Debug, Serialize)]
+pub struct PageLinkChanges {
+    page: PageId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    added: usize,
+    removed: usize,
+}
+
+/// The in-degree of a page in both databases.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PageDegreeChange {
+    page: PageId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    old_in_degree: usize,
+    new_in_degree: usize,
+}
+
+/// Count the pages in only the old and only the new list of (numerically sorted) adjacent pages.
+fn count_list_changes(old: &[PageId], new: &[PageId]) -> (usize, usize) {
+    let (mut i, mut j) = (0, 0);
+    let (mut added, mut removed) = (0, 0);
+    while i < old.len() && j < new.len() {
+        match old[i].cmp(&new[j]) {
+            Ordering::Less => {
+                removed += 1;
+                i += 1;
+            }
+            Ordering::Greater => {
+                added += 1;
+                j += 1;
+            }
+            Ordering::Equal => {
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    (added + new.len() - j, removed + old.len() - i)
+}
+
+impl Database {
+    /// Compare two databases, listing the `top` pages with the most changed links and in-degree.
+    /// The tables of both databases are iterated side by side in key order, so memory use is
+    /// bounded.
+    pub fn diff(old: &Database, new: &Database, top: usize) -> Result<DatabaseDiff> {
+        if old.metadata.language_code != new.metadata.language_code {
+            log::warn!(
+                "comparing databases of different languages '{}' and '{}'",
+                old.metadata.language_code,
+                new.metadata.language_code
+            );
+        }
+
+        let old_txn = old.read_txn()?;
+        let new_txn = new.read_txn()?;
+
+        log::info!("comparing pages");
+        let mut pages = Changes::default();
+        let mut most_changed_in_degree = TopPages::new(top);
+        let old_pages = page_in_degrees(old, &old_txn)?;
+        let new_pages = page_in_degrees(new, &new_txn)?;
+        for entry in merge_by_key(old_pages, new_pages) {
+            let (page, old_in_degree, new_in_degree) = entry?;
+            pages.add(old_in_degree.is_some(), new_in_degree.is_some());
+            let old_in_degree = old_in_degree.unwrap_or(0);
+            let new_in_degree = new_in_degree.unwrap_or(0);
+            most_changed_in_degree.add(
+                page,
+                old_in_degree.abs_diff(new_in_degree),
+                (old_in_degree, new_in_degree),
+            );
+        }
+
+        log::info!("comparing links");
+        let mut links = Changes::default();
+        let mut most_changed_links = TopPages::new(top);
+        for entry in merge_by_key(old.iter_outgoing(&old_txn)?, new.iter_outgoing(&new_txn)?) {
+            let (page, old_targets, new_targets) = entry?;
+            let old_targets = old_targets.unwrap_or_default();
+            let new_targets = new_targets.unwrap_or_default();
+            let (added, removed) = count_list_changes(&old_targets, &new_targets);
+            links.old += old_targets.len() as u64;
+            links.new += new_targets.len() as u64;
+            links.added += added as u64;
+            links.removed += removed as u64;
+            most_changed_links.add(page, added + removed, (added, removed));
+        }
+
+        log::info!("comparing redirects");
+        let mut redirects = Changes::default();
+        let mut redirects_retargeted = 0;
+        for entry in merge_by_key(old.iter_redirects(&old_txn)?, new.iter_redirects(&new_txn)?) {
+            let (_, old_target, new_target) = entry?;
+            redirects.add(old_target.is_some(), new_target.is_some());
+            if old_target
+                .zip(new_target)
+                .is_some_and(|(old, new)| old != new)
+            {
+                redirects_retargeted += 1;
+            }
+        }
+
+        // titles are taken from the new database, falling back to the old one for removed pages
+        let title = |page| -> Result<Option<String>> {
+            match new.get_title(&new_txn, page)? {
+                Some(title) => Ok(Some(title)),
+                None => old.get_title(&old_txn, page),
+            }
+        };
+        let most_changed_links = most_changed_links
+            .into_pages()
+            .into_iter()
+            .filter(|(_, changes, _)| *changes > 0)
+            .map(|(page, _, (added, removed))| {
+                Ok(PageLinkChanges {
+                    page,
+                    title: title(page)?,
+                    added,
+                    removed,
+                })
+            })
+            .collect::<Result<_>>()?;
+        let most_changed_in_degree = most_changed_in_degree
+            .into_pages()
+            .into_iter()
+            .filter(|(_, change, _)| *change > 0)
+            .map(|(page, _, (old_in_degree, new_in_degree))| {
+                Ok(PageDegreeChange {
+                    page,
+                    title: title(page)?,
+                    old_in_degree,
+                    new_in_degree,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        old_txn.commit()?;
+        new_txn.commit()?;
+
+        Ok(DatabaseDiff {
+            old: old.metadata.clone(),
+            new: new.metadata.clone(),
+            pages,
+            links,
+            redirects,
+            redirects_retargeted,
+            most_changed_links,
+            most_changed_in_degree,
+        })
+    }
+}
+
+/// Iterate over the pages of a database as (page, in-degree) pairs, in key order.
+fn page_in_degrees<'t>(
+    db: &Database,
+    txn: &'t RoTxn<'_>,
+) -> Result<impl Iterator<Item = Result<(PageId, usize)>> + 't> {
+    Ok(db.iter_pages(txn)?.map(|entry| {
+        let (page, in_degree, _) = entry?;
+        Ok((page, in_degree))
+    }))
+}
+
+fn format_changes(f: &mut fmt::Formatter<'_>, name: &str, changes: &Changes) -> fmt::Result {
+    writeln!(
+        f,
+        "{name:<10} {} -> {} (+{}, -{})",
+        changes.old, changes.new, changes.added, changes.removed
+    )
+}
+
+impl fmt::Display for DatabaseDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let with_title = |page: PageId, title: &Option<String>| match title {
+            Some(title) => format!("{page} ({title})"),
+            None => page.to_string(),
+        };
+
+        writeln!(f, "old:       {}", self.old.to_name())?;
+        writeln!(f, "new:       {}", self.new.to_name())?;
+        writeln!(f)?;
+        format_changes(f, "pages:", &self.pages)?;
+        format_changes(f, "links:", &self.links)?;
+        format_changes(f, "redirects:", &self.redirects)?;
+        writeln!(
+            f,
+            "           {} redirects retargeted",
+            self.redirects_retargeted
+        )?;
+
+        writeln!(f)?;
+        writeln!(f, "pages with most changed links:")?;
+        for page in &self.most_changed_links {
+            writeln!(
+                f,
+                "  {:>10} {:>10}  {}",
+                format!("+{}", page.added),
+                format!("-{}", page.removed),
+                with_title(page.page, &page.title)
+            )?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "pages with most changed in-degree:")?;
+        for page in &self.most_changed_in_degree {
+            writeln!(
+                f,
+                "  {:>10} -> {:<10}  {}",
+                page.old_in_degree,
+                page.new_in_degree,
+                with_title(page.page, &page.title)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_added_and_removed_list_entries() {
+        assert_eq!(count_list_changes(&[], &[]), (0, 0));
+        assert_eq!(count_list_changes(&[1, 2, 3], &[1, 2, 3]), (0, 0));
+        assert_eq!(count_list_changes(&[1, 3, 5], &[2, 3, 6, 7]), (3, 2));
+        assert_eq!(count_list_changes(&[1, 2], &[]), (0, 2));
+    }
+}
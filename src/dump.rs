@@ -1,17 +1,35 @@
-use crate::database::Metadata;
+use crate::{database::Metadata, lock::BuildLock, misc::format_bytes};
 use anyhow::{anyhow, bail, Context, Result};
 use data_encoding::HEXLOWER;
+use humantime::format_duration;
 use regex::Regex;
 use ring::digest;
 use std::{
+    collections::{BTreeMap, BTreeSet},
     fs::{self, File},
     io::{BufReader, Read, Write},
+    ops::Bound::{Excluded, Unbounded},
     path::{Path, PathBuf},
-    time::Duration,
+    time::{Duration, Instant},
 };
+use tokio::sync::Semaphore;
+
+/// Maximum number of simultaneous connections to the dumps server. Wikimedia limits the number of
+/// concurrent connections per IP address on <https://dumps.wikimedia.org>.
+pub const MAX_CONNECTIONS: usize = 2;
+
+/// Robot policy "Rules for other resources": use a delay of at least 1 second between requests.
+/// <https://wikitech.wikimedia.org/wiki/Robot_policy#Rules_for_other_resources>
+const REQUEST_DELAY: Duration = Duration::from_secs(1);
+
+/// Directory within the dumps directory where dump files failing verification are moved to.
+const QUARANTINE_DIR: &str = ".quarantine";
+
+/// Interval at which the progress of running downloads is logged.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
 
 /// User-Agent string identifying this bot per the Wikimedia Foundation User-Agent Policy.
-/// https://foundation.wikimedia.org/wiki/Policy:Wikimedia_Foundation_User-Agent_Policy
+/// <https://foundation.wikimedia.org/wiki/Policy:Wikimedia_Foundation_User-Agent_Policy>
 /// Format: <client name>/<version> (<contact information>)
 const USER_AGENT: &str = concat!(
     "Wikipath/",
@@ -20,7 +38,7 @@ const USER_AGENT: &str = concat!(
 );
 
 /// Build a `reqwest::Client` configured for compliance with the Wikimedia Robot policy
-/// (https://wikitech.wikimedia.org/wiki/Robot_policy):
+/// (<https://wikitech.wikimedia.org/wiki/Robot_policy>):
 /// - Descriptive User-Agent header (User-Agent Policy, rule #2).
 /// - gzip transport compression (Robot policy "Generally applicable rules", rule #5 "Default to gzip").
 /// - Connect timeout so hung connections don't stall builds indefinitely.
@@ -33,7 +51,7 @@ pub fn build_client() -> Result<reqwest::Client> {
 }
 
 /// Send a request with retry handling that complies with the Wikimedia Robot policy
-/// (https://wikitech.wikimedia.org/wiki/Robot_policy):
+/// (<https://wikitech.wikimedia.org/wiki/Robot_policy>):
 /// - On `429 Too Many Requests`, honor the `Retry-After` header before retrying
 ///   (Robot policy "Generally applicable rules", rule #6 "Respect our HTTP status codes").
 /// - On `5xx`, use a short exponential backoff (30s, 60s, 120s) before giving up
@@ -112,6 +130,7 @@ struct TableDumpFileMetadata {
     r#type: String,
     language_code: String,
     date_code: String,
+    extension: String,
     hash: String,
 }
 
@@ -124,19 +143,23 @@ impl TableDumpFileMetadata {
         }
     }
 
-    /// Create a metadata struct from a full filename and a hash.
+    /// Create a metadata struct from a full filename and a hash. The dump may be uncompressed
+    /// (`.sql`) or compressed using gzip (`.sql.gz`), bzip2 (`.sql.bz2`) or zstd (`.sql.zst`).
     pub fn from_full_name_and_hash(full_name: &str, hash: String) -> Result<Self> {
-        let re = Regex::new(r"^([a-zA-Z]+)wiki-([0-9]+)-(.+).sql.gz$")?;
+        let re = Regex::new(r"^([a-zA-Z]+)wiki-([0-9]+)-(.+?)\.(sql(?:\.gz|\.bz2|\.zst)?)$")?;
         if let Some(caps) = re.captures(full_name) {
             if let Some(language_code) = caps.get(1) {
                 if let Some(date_code) = caps.get(2) {
                     if let Some(typ) = caps.get(3) {
-                        return Ok(Self {
-                            r#type: typ.as_str().to_string(),
-                            language_code: language_code.as_str().to_string(),
-                            date_code: date_code.as_str().to_string(),
-                            hash,
-                        });
+                        if let Some(extension) = caps.get(4) {
+                            return Ok(Self {
+                                r#type: typ.as_str().to_string(),
+                                language_code: language_code.as_str().to_string(),
+                                date_code: date_code.as_str().to_string(),
+                                extension: extension.as_str().to_string(),
+                                hash,
+                            });
+                        }
                     }
                 }
             }
@@ -147,8 +170,8 @@ impl TableDumpFileMetadata {
     /// Convert the metadata to a full filename.
     pub fn to_full_name(&self) -> String {
         format!(
-            "{}wiki-{}-{}.sql.gz",
-            self.language_code, self.date_code, self.r#type
+            "{}wiki-{}-{}.{}",
+            self.language_code, self.date_code, self.r#type, self.extension
         )
     }
 }
@@ -166,9 +189,86 @@ impl TableDumpFileMetadatas {
     pub fn to_normal(&self) -> Metadata {
         self.page.to_normal() // just pick one, they should all be the same
     }
+
+    /// Get the metadata of a dump file by its table type.
+    fn get(&self, r#type: &str) -> Option<&TableDumpFileMetadata> {
+        [
+            &self.page,
+            &self.redirect,
+            &self.pagelinks,
+            &self.linktarget,
+        ]
+        .into_iter()
+        .find(|md| md.r#type == r#type)
+    }
 }
 
 impl TableDumpFiles {
+    /// Create a set of dump files from local paths, e.g. mysqldump output of a self-hosted wiki
+    /// that does not follow the Wikimedia dump naming. If a sums file is given, in the
+    /// `<sha1>  <filename>` format of Wikimedia, the files are verified against it.
+    pub fn from_local(
+        page: PathBuf,
+        redirect: PathBuf,
+        pagelinks: PathBuf,
+        linktarget: PathBuf,
+        sha1sums: Option<&Path>,
+    ) -> Result<Self> {
+        let files = Self {
+            page,
+            redirect,
+            pagelinks,
+            linktarget,
+        };
+
+        for path in [
+            &files.page,
+            &files.redirect,
+            &files.pagelinks,
+            &files.linktarget,
+        ] {
+            if !path.is_file() {
+                bail!("dump file '{}' does not exist", path.display());
+            }
+        }
+
+        if let Some(sha1sums) = sha1sums {
+            log::info!("checking dump file hashes");
+            let sums = fs::read_to_string(sha1sums)?;
+            for path in [
+                &files.page,
+                &files.redirect,
+                &files.pagelinks,
+                &files.linktarget,
+            ] {
+                let filename = path.file_name().and_then(|s| s.to_str()).context(format!(
+                    "dump file filename in path '{}' is not valid",
+                    path.display()
+                ))?;
+                let hash = sums
+                    .lines()
+                    .filter_map(|line| line.split_once("  "))
+                    .find(|(_, name)| *name == filename)
+                    .map(|(hash, _)| hash)
+                    .context(format!("dump file '{filename}' missing in sums file"))?;
+
+                let mut context = digest::Context::new(&digest::SHA1_FOR_LEGACY_USE_ONLY);
+                update_hash_from_file(&mut context, path)?;
+                let digest = HEXLOWER.encode(context.finish().as_ref());
+                if digest != hash {
+                    bail!(
+                        "file '{}' hash mismatch between digest {} and target {}",
+                        path.display(),
+                        digest,
+                        hash
+                    );
+                }
+            }
+        }
+
+        Ok(files)
+    }
+
     /// Get metadatas of the dump files from Wikimedia. The date code may be "latest".
     pub async fn get_metadatas(
         client: &reqwest::Client,
@@ -179,7 +279,7 @@ impl TableDumpFiles {
             "https://dumps.wikimedia.org/{language_code}wiki/{date_code}/{language_code}wiki-{date_code}-sha1sums.txt"
         );
 
-        let resp = send_with_retry(client.get(&url).timeout(Duration::from_secs(60))).await?;
+        let resp = send_with_retry(client.get(&url).timeout(Duration::from_mins(1))).await?;
         let lines = resp.text().await?;
 
         let lines_split = lines
@@ -231,28 +331,25 @@ impl TableDumpFiles {
         })
     }
 
-    /// Download all relevant dump files from Wikimedia into a directory.
+    /// Download all relevant dump files from Wikimedia into a directory. Files are fetched
+    /// concurrently within the connection limit of the given options, and their hashes are
+    /// verified while streaming.
     pub async fn download(
         client: &reqwest::Client,
         dumps_dir: &Path,
         metadatas: TableDumpFileMetadatas,
+        options: &DownloadOptions,
     ) -> Result<Self> {
         log::info!("downloading dump files");
-        let page = Self::download_single(client, dumps_dir, &metadatas.page).await?;
-        // Robot policy "Rules for other resources": use a delay of at least 1 second between requests.
-        // https://wikitech.wikimedia.org/wiki/Robot_policy#Rules_for_other_resources
-        tokio::time::sleep(Duration::from_secs(1)).await;
-        let redirect = Self::download_single(client, dumps_dir, &metadatas.redirect).await?;
-        tokio::time::sleep(Duration::from_secs(1)).await;
-        let pagelinks = Self::download_single(client, dumps_dir, &metadatas.pagelinks).await?;
-        tokio::time::sleep(Duration::from_secs(1)).await;
-        let linktarget = Self::download_single(client, dumps_dir, &metadatas.linktarget).await?;
-
-        log::info!("checking dump file hashes");
-        check_file_hash(&page, &metadatas.page.hash)?;
-        check_file_hash(&redirect, &metadatas.redirect.hash)?;
-        check_file_hash(&pagelinks, &metadatas.pagelinks.hash)?;
-        check_file_hash(&linktarget, &metadatas.linktarget.hash)?;
+        fs::create_dir_all(dumps_dir)?;
+
+        let downloader = Downloader::new(client, dumps_dir, options);
+        let (page, redirect, pagelinks, linktarget) = tokio::try_join!(
+            downloader.download_single(&metadatas.page),
+            downloader.download_single(&metadatas.redirect),
+            downloader.download_single(&metadatas.pagelinks),
+            downloader.download_single(&metadatas.linktarget),
+        )?;
 
         Ok(Self {
             page,
@@ -262,56 +359,155 @@ impl TableDumpFiles {
         })
     }
 
-    /// Download a single file from Wikimedia into a directory.
-    async fn download_single(
+    /// Print the dump files present in the given directory, grouped by language and date.
+    pub fn list_cache(dir: &Path) -> Result<()> {
+        let files = cached_dump_files(dir)?;
+        if files.is_empty() {
+            println!("no dump files found in '{}'", dir.display());
+        }
+
+        let mut previous: Option<Metadata> = None;
+        for (md, path) in &files {
+            let normal = md.to_normal();
+            if previous.as_ref() != Some(&normal) {
+                println!("{}wiki {}", normal.language_code, normal.date_code);
+                previous = Some(normal);
+            }
+            println!(
+                "  {:<12} {:>12}  {}",
+                md.r#type,
+                format_bytes(fs::metadata(path)?.len()),
+                path.display()
+            );
+        }
+
+        let quarantined = quarantined_dump_files(dir)?;
+        if !quarantined.is_empty() {
+            println!("quarantined");
+            for path in quarantined {
+                println!(
+                    "  {:>12}  {}",
+                    format_bytes(fs::metadata(&path)?.len()),
+                    path.display()
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Verify the dump files present in the given directory against the hashes published by
+    /// Wikimedia. Files that do not match are reported, and optionally quarantined. Files of which
+    /// the hashes are no longer published are reported as unverifiable. Returns an error if any
+    /// mismatch was found.
+    pub async fn verify_cache(
         client: &reqwest::Client,
-        dumps_dir: &Path,
-        metadata: &TableDumpFileMetadata,
-    ) -> Result<PathBuf> {
-        let target = dumps_dir.join(metadata.to_full_name());
-        let mut file = {
-            if target.exists() {
-                File::options().append(true).open(&target)
+        dir: &Path,
+        quarantine: bool,
+    ) -> Result<()> {
+        let files = cached_dump_files(dir)?;
+        let mut mismatches = 0;
+        let mut unverifiable = 0;
+
+        let mut remote: Option<(Metadata, Option<TableDumpFileMetadatas>)> = None;
+        for (md, path) in files {
+            let normal = md.to_normal();
+            let metadatas = match &remote {
+                Some((m, metadatas)) if *m == normal => metadatas,
+                _ => {
+                    let metadatas =
+                        Self::get_metadatas(client, &normal.language_code, &normal.date_code)
+                            .await
+                            .inspect_err(|e| {
+                                log::warn!(
+                                    "could not get hashes of {}wiki {}: {e}",
+                                    normal.language_code,
+                                    normal.date_code
+                                );
+                            })
+                            .ok();
+                    &remote.insert((normal, metadatas)).1
+                }
+            };
+            let Some(metadatas) = metadatas else {
+                unverifiable += 1;
+                log::warn!("dump file '{}' is unverifiable", path.display());
+                continue;
+            };
+
+            let Some(expected) = metadatas.get(&md.r#type) else {
+                log::debug!("skipping unused dump file '{}'", path.display());
+                continue;
+            };
+
+            let mut context = digest::Context::new(&digest::SHA1_FOR_LEGACY_USE_ONLY);
+            update_hash_from_file(&mut context, &path)?;
+            let digest = HEXLOWER.encode(context.finish().as_ref());
+            if digest == expected.hash {
+                log::info!("dump file '{}' is valid", path.display());
             } else {
-                std::fs::create_dir_all(dumps_dir)?;
-                File::create(&target)
+                mismatches += 1;
+                log::warn!(
+                    "file '{}' hash mismatch between digest {} and target {}",
+                    path.display(),
+                    digest,
+                    expected.hash
+                );
+                if quarantine {
+                    quarantine_dump_file(dir, &path)?;
+                }
             }
-        }?;
+        }
 
-        let url = format!(
-            "https://dumps.wikimedia.org/{}wiki/{}/{}",
-            metadata.language_code,
-            metadata.date_code,
-            metadata.to_full_name(),
-        );
+        if mismatches > 0 {
+            bail!("{mismatches} dump file(s) failed verification");
+        }
 
-        let head_resp = send_with_retry(client.head(&url)).await?;
-        let existing_bytes = file.metadata()?.len();
-        let total_bytes = head_resp
-            .headers()
-            .get(reqwest::header::CONTENT_LENGTH)
-            .and_then(|h| h.to_str().ok().and_then(|s| s.parse().ok()))
-            .context(format!("missing Content-Length header at '{url}'"))?;
+        if unverifiable > 0 {
+            log::warn!("{unverifiable} dump file(s) could not be verified");
+        } else {
+            log::info!("all dump files are valid");
+        }
+        Ok(())
+    }
 
-        if existing_bytes < total_bytes {
-            let mut resp = send_with_retry(
-                client
-                    .get(&url)
-                    .header(reqwest::header::RANGE, format!("bytes={existing_bytes}-")),
-            )
-            .await?;
+    /// Prune the given directory, removing quarantined files and keeping only the dump files of
+    /// the given number of most recent dates for each language. At least one date is kept.
+    pub fn prune_cache(dir: &Path, keep: usize) -> Result<()> {
+        if keep == 0 {
+            bail!("at least one date has to be kept for each language");
+        }
+        let files = cached_dump_files(dir)?;
+
+        let mut dates: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+        for (md, _) in &files {
+            dates
+                .entry(&md.language_code)
+                .or_default()
+                .insert(&md.date_code);
+        }
 
-            while let Some(chunk) = resp.chunk().await? {
-                file.write_all(&chunk)?;
+        for (md, path) in &files {
+            let newer_dates = dates[md.language_code.as_str()]
+                .range::<&str, _>((Excluded(md.date_code.as_str()), Unbounded))
+                .count();
+            if newer_dates >= keep {
+                fs::remove_file(path)?;
+                log::info!("removed dump file '{}'", path.display());
             }
+        }
 
-            file.flush()?;
+        let quarantine_dir = dir.join(QUARANTINE_DIR);
+        if quarantine_dir.exists() {
+            fs::remove_dir_all(&quarantine_dir)?;
+            log::info!("removed quarantined dump files");
         }
 
-        Ok(target)
+        Ok(())
     }
 
-    /// Remove dump files with different date in the given directory.
+    /// Remove dump files with different date in the given directory. Dump files of databases that
+    /// are still being built according to their build lock in the directory are skipped.
     pub fn remove_different_date_dump_files(anchor: &Metadata, dir: &Path) -> Result<()> {
         for entry in fs::read_dir(dir)? {
             let path = entry?.path();
@@ -324,6 +520,13 @@ impl TableDumpFiles {
                 Ok(md) => {
                     if md.language_code == anchor.language_code && md.date_code != anchor.date_code
                     {
+                        let Some(_lock) = BuildLock::try_acquire(&md.to_normal(), dir)? else {
+                            log::warn!(
+                                "skipping dump file with different date '{}' that is being used",
+                                path.display()
+                            );
+                            continue;
+                        };
                         fs::remove_file(&path)?;
                         log::info!("removed dump file with different date '{}'", path.display());
                     }
@@ -338,11 +541,351 @@ impl TableDumpFiles {
     }
 }
 
-/// Check whether the hash of a file matches with a given hash.
-fn check_file_hash(path: &Path, hash: &str) -> Result<()> {
+/// Options controlling how dump files are downloaded.
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+    /// Maximum number of files downloaded simultaneously. Capped at `MAX_CONNECTIONS`.
+    pub connections: usize,
+    /// Optional cap on the combined download rate of all files in bytes per second.
+    pub rate_limit: Option<u64>,
+}
+
+/// Shared state of the dump files being downloaded concurrently.
+struct Downloader<'a> {
+    client: &'a reqwest::Client,
+    dumps_dir: &'a Path,
+    connections: Semaphore,
+    last_request: tokio::sync::Mutex<Option<Instant>>,
+    throttle: Option<Throttle>,
+}
+
+impl<'a> Downloader<'a> {
+    fn new(client: &'a reqwest::Client, dumps_dir: &'a Path, options: &DownloadOptions) -> Self {
+        if options.connections > MAX_CONNECTIONS {
+            log::warn!(
+                "limiting download connections from {} to {MAX_CONNECTIONS} to respect the Wikimedia robot policy",
+                options.connections
+            );
+        }
+
+        Self {
+            client,
+            dumps_dir,
+            connections: Semaphore::new(options.connections.clamp(1, MAX_CONNECTIONS)),
+            last_request: tokio::sync::Mutex::new(None),
+            throttle: options.rate_limit.map(Throttle::new),
+        }
+    }
+
+    /// Wait until at least `REQUEST_DELAY` has passed since the previous request was started.
+    async fn pace(&self) {
+        let mut last_request = self.last_request.lock().await;
+        if let Some(last) = *last_request {
+            tokio::time::sleep_until((last + REQUEST_DELAY).into()).await;
+        }
+        *last_request = Some(Instant::now());
+    }
+
+    /// Download a single file from Wikimedia into the dumps directory, resuming any partial
+    /// download from a previous run. If the resulting file does not match the hash in the
+    /// metadata, it is quarantined and downloaded once more from scratch.
+    async fn download_single(&self, metadata: &TableDumpFileMetadata) -> Result<PathBuf> {
+        let _permit = self.connections.acquire().await?;
+        let target = self.dumps_dir.join(metadata.to_full_name());
+
+        let digest = self.fetch_single(metadata, &target).await?;
+        if digest == metadata.hash {
+            return Ok(target);
+        }
+
+        log::warn!(
+            "file '{}' hash mismatch between digest {} and target {}, downloading again",
+            target.display(),
+            digest,
+            metadata.hash
+        );
+        quarantine_dump_file(self.dumps_dir, &target)?;
+
+        let digest = self.fetch_single(metadata, &target).await?;
+        if digest != metadata.hash {
+            bail!(
+                "file '{}' hash mismatch between digest {} and target {} after downloading again",
+                target.display(),
+                digest,
+                metadata.hash
+            );
+        }
+
+        Ok(target)
+    }
+
+    /// Fetch a single file into the target path, continuing from any bytes already present.
+    /// Returns the hex-encoded SHA-1 digest of the complete file.
+    async fn fetch_single(
+        &self,
+        metadata: &TableDumpFileMetadata,
+        target: &Path,
+    ) -> Result<String> {
+        let name = metadata.to_full_name();
+        let url = format!(
+            "https://dumps.wikimedia.org/{}wiki/{}/{}",
+            metadata.language_code, metadata.date_code, name,
+        );
+
+        self.pace().await;
+        let head_resp = send_with_retry(self.client.head(&url)).await?;
+        let total_bytes = head_resp
+            .headers()
+            .get(reqwest::header::CONTENT_LENGTH)
+            .and_then(|h| h.to_str().ok().and_then(|s| s.parse().ok()))
+            .context(format!("missing Content-Length header at '{url}'"))?;
+
+        // A file larger than the remote one cannot be a partial download, so it is set aside.
+        if target.exists() && fs::metadata(target)?.len() > total_bytes {
+            log::warn!(
+                "dump file '{}' is larger than the remote file, downloading again",
+                target.display()
+            );
+            quarantine_dump_file(self.dumps_dir, target)?;
+        }
+
+        let mut file = File::options().create(true).append(true).open(target)?;
+        let mut existing_bytes = file.metadata()?.len();
+
+        // Bytes from a previous run are hashed up front, so that the digest covers the full file.
+        let mut context = digest::Context::new(&digest::SHA1_FOR_LEGACY_USE_ONLY);
+        if existing_bytes > 0 {
+            update_hash_from_file(&mut context, target)?;
+        }
+
+        if existing_bytes < total_bytes {
+            self.pace().await;
+            let mut resp = send_with_retry(
+                self.client
+                    .get(&url)
+                    .header(reqwest::header::RANGE, format!("bytes={existing_bytes}-")),
+            )
+            .await?;
+
+            // If the server ignored the range request, it sends the whole file, so start over.
+            if existing_bytes > 0 && resp.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+                log::warn!("range request for '{name}' not honored, restarting download");
+                file.set_len(0)?;
+                existing_bytes = 0;
+                context = digest::Context::new(&digest::SHA1_FOR_LEGACY_USE_ONLY);
+            }
+
+            let mut progress = Progress::new(&name, existing_bytes, total_bytes);
+            while let Some(chunk) = resp.chunk().await? {
+                file.write_all(&chunk)?;
+                context.update(&chunk);
+                progress.advance(chunk.len() as u64);
+                if let Some(throttle) = &self.throttle {
+                    throttle.consume(chunk.len() as u64).await;
+                }
+            }
+
+            file.flush()?;
+            progress.finish();
+        } else {
+            log::info!("dump file '{name}' already downloaded");
+        }
+
+        Ok(HEXLOWER.encode(context.finish().as_ref()))
+    }
+}
+
+/// Rate limiter shared between downloads, capping their combined throughput.
+struct Throttle {
+    bytes_per_second: u64,
+    next: std::sync::Mutex<Instant>,
+}
+
+impl Throttle {
+    fn new(bytes_per_second: u64) -> Self {
+        Self {
+            bytes_per_second: bytes_per_second.max(1),
+            next: std::sync::Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Account for a number of received bytes, sleeping until they fit within the rate limit.
+    #[allow(clippy::cast_precision_loss)]
+    async fn consume(&self, bytes: u64) {
+        let until = {
+            let mut next = self.next.lock().unwrap();
+            let cost = Duration::from_secs_f64(bytes as f64 / self.bytes_per_second as f64);
+            *next = (*next).max(Instant::now()) + cost;
+            *next
+        };
+        tokio::time::sleep_until(until.into()).await;
+    }
+}
+
+/// Periodic progress reporting of a single download.
+struct Progress<'a> {
+    name: &'a str,
+    resumed_from: u64,
+    done: u64,
+    total: u64,
+    start: Instant,
+    last_report: Instant,
+}
+
+impl<'a> Progress<'a> {
+    fn new(name: &'a str, resumed_from: u64, total: u64) -> Self {
+        if resumed_from > 0 {
+            log::info!(
+                "resuming download of '{name}' at {} of {}",
+                format_bytes(resumed_from),
+                format_bytes(total)
+            );
+        } else {
+            log::info!("downloading '{name}' ({})", format_bytes(total));
+        }
+
+        let now = Instant::now();
+        Self {
+            name,
+            resumed_from,
+            done: resumed_from,
+            total,
+            start: now,
+            last_report: now,
+        }
+    }
+
+    fn advance(&mut self, bytes: u64) {
+        self.done += bytes;
+        if self.last_report.elapsed() >= PROGRESS_INTERVAL {
+            self.last_report = Instant::now();
+            self.report();
+        }
+    }
+
+    /// Bytes per second transferred since the start of this download.
+    #[allow(clippy::cast_precision_loss)]
+    fn rate(&self) -> f64 {
+        let elapsed = self.start.elapsed().as_secs_f64();
+        if elapsed > 0.0 {
+            (self.done - self.resumed_from) as f64 / elapsed
+        } else {
+            0.0
+        }
+    }
+
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    fn report(&self) {
+        let rate = self.rate();
+        let remaining = self.total.saturating_sub(self.done);
+        let eta = if rate > 0.0 {
+            format_duration(Duration::from_secs((remaining as f64 / rate) as u64)).to_string()
+        } else {
+            "unknown".to_string()
+        };
+        let percentage = if self.total > 0 {
+            self.done as f64 / self.total as f64 * 100.0
+        } else {
+            100.0
+        };
+
+        log::info!(
+            "downloading '{}': {} of {} ({percentage:.1}%), {}/s, eta {eta}",
+            self.name,
+            format_bytes(self.done),
+            format_bytes(self.total),
+            format_bytes(rate as u64),
+        );
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn finish(&self) {
+        log::info!(
+            "downloaded '{}' in {} ({}/s)",
+            self.name,
+            format_duration(Duration::from_secs(self.start.elapsed().as_secs())),
+            format_bytes(self.rate() as u64),
+        );
+    }
+}
+
+/// Move a dump file into the quarantine directory within the dumps directory, replacing any
+/// previously quarantined file with the same name.
+fn quarantine_dump_file(dumps_dir: &Path, path: &Path) -> Result<()> {
+    let quarantine_dir = dumps_dir.join(QUARANTINE_DIR);
+    fs::create_dir_all(&quarantine_dir)?;
+
+    let filename = path.file_name().context(format!(
+        "dump file path '{}' has no filename",
+        path.display()
+    ))?;
+    let destination = quarantine_dir.join(filename);
+    if destination.exists() {
+        fs::remove_file(&destination)?;
+    }
+
+    fs::rename(path, &destination)?;
+    log::warn!(
+        "quarantined dump file '{}' to '{}'",
+        path.display(),
+        destination.display()
+    );
+    Ok(())
+}
+
+/// List the dump files in a directory, sorted by language, date and type.
+fn cached_dump_files(dir: &Path) -> Result<Vec<(TableDumpFileMetadata, PathBuf)>> {
+    let mut files = Vec::new();
+    if !dir.exists() {
+        return Ok(files);
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        let Some(filename) = path.file_name().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        match TableDumpFileMetadata::from_full_name_and_hash(filename, String::new()) {
+            Ok(md) => files.push((md, path)),
+            Err(_) => log::debug!("skipping non-dump file path '{}'", path.display()),
+        }
+    }
+
+    files.sort_by(|(a, _), (b, _)| {
+        (&a.language_code, &a.date_code, &a.r#type).cmp(&(
+            &b.language_code,
+            &b.date_code,
+            &b.r#type,
+        ))
+    });
+    Ok(files)
+}
+
+/// List the files in the quarantine directory within a dumps directory.
+fn quarantined_dump_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let quarantine_dir = dir.join(QUARANTINE_DIR);
+    if !quarantine_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut files = fs::read_dir(quarantine_dir)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<_>>>()?;
+    files.sort();
+    Ok(files)
+}
+
+/// Feed the full contents of a file into a digest context.
+fn update_hash_from_file(context: &mut digest::Context, path: &Path) -> Result<()> {
     let file = File::open(path)?;
     let mut reader = BufReader::new(&file);
-    let mut context = digest::Context::new(&digest::SHA1_FOR_LEGACY_USE_ONLY);
     let mut buffer = [0; 8192];
 
     loop {
@@ -353,15 +896,5 @@ fn check_file_hash(path: &Path, hash: &str) -> Result<()> {
         context.update(&buffer[..count]);
     }
 
-    let digest = HEXLOWER.encode(context.finish().as_ref());
-    if digest != hash {
-        bail!(
-            "file '{}' hash mismatch between digest {} and target {}",
-            path.display(),
-            digest,
-            hash
-        );
-    }
-
     Ok(())
 }
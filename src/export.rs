@@ -0,0 +1,433 @@
+use crate::{
+    database::{Database, PageId, PageNamespaceId},
+    misc::PageSet,
+};
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use heed::RoTxn;
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+/// The formats in which a database can be exported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// Tab-separated lines of `link<TAB>source<TAB>target`, `redirect<TAB>source<TAB>target` and
+    /// `title<TAB>page<TAB>title`.
+    Tsv,
+    /// `GraphML` document with titles and redirect flags as node data.
+    Graphml,
+    /// Graphviz DOT digraph, with redirects drawn dashed.
+    Dot,
+    /// Directory with `nodes.csv` and `relationships.csv` files for `neo4j-admin database import`.
+    Neo4jCsv,
+}
+
+/// Filters restricting which pages are exported. Links are only exported if both of their pages
+/// pass the filter. Redirects are exported if their target passes the filter, and their source
+/// passes the page and namespace filters (they have no links themselves). Every page of an
+/// exported link or redirect is exported as a node.
+#[derive(Debug, Default)]
+pub struct ExportFilter {
+    pub pages: Option<HashSet<PageId>>,
+    pub namespaces: Option<HashSet<PageNamespaceId>>,
+    pub min_in_degree: Option<usize>,
+    pub max_in_degree: Option<usize>,
+    pub min_out_degree: Option<usize>,
+    pub max_out_degree: Option<usize>,
+}
+
+impl ExportFilter {
+    /// Whether a page passes the page and namespace filters.
+    fn includes_page(&self, db: &Database, txn: &RoTxn<'_>, page: PageId) -> Result<bool> {
+        if let Some(pages) = &self.pages {
+            if !pages.contains(&page) {
+                return Ok(false);
+            }
+        }
+
+        if let Some(namespaces) = &self.namespaces {
+            match db.get_namespace(txn, page)? {
+                Some(namespace) if namespaces.contains(&namespace) => {}
+                _ => return Ok(false),
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Whether a (non-redirect) page with the given degrees passes all filters.
+    fn includes(
+        &self,
+        db: &Database,
+        txn: &RoTxn<'_>,
+        page: PageId,
+        in_degree: usize,
+        out_degree: usize,
+    ) -> Result<bool> {
+        Ok(within(in_degree, self.min_in_degree, self.max_in_degree)
+            && within(out_degree, self.min_out_degree, self.max_out_degree)
+            && self.includes_page(db, txn, page)?)
+    }
+}
+
+fn within(value: usize, min: Option<usize>, max: Option<usize>) -> bool {
+    min.is_none_or(|min| value >= min) && max.is_none_or(|max| value <= max)
+}
+
+/// A writer of a graph in some format. Nodes are always written before any edges.
+trait GraphWriter {
+    fn node(&mut self, page: PageId, title: Option<&str>, redirect: bool) -> Result<()>;
+    fn link(&mut self, source: PageId, target: PageId) -> Result<()>;
+    fn redirect(&mut self, source: PageId, target: PageId) -> Result<()>;
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+impl Database {
+    /// Export the links and redirects (and titles, if present) of the database in some format
+    /// to a file or directory, or to stdout if no output path is given. The tables are streamed
+    /// in two passes, one for the nodes and one for the edges. Only the set of exported pages is
+    /// kept in memory, to decide which edges to export.
+    pub fn export(
+        &self,
+        format: ExportFormat,
+        filter: &ExportFilter,
+        output: Option<&Path>,
+    ) -> Result<()> {
+        let name = self.metadata.to_name();
+        let mut writer: Box<dyn GraphWriter> = match format {
+            ExportFormat::Neo4jCsv => {
+                let Some(dir) = output else {
+                    bail!("the neo4j-csv format requires an output directory");
+                };
+                Box::new(Neo4jCsvWriter::new(dir)?)
+            }
+            ExportFormat::Tsv => Box::new(TsvWriter {
+                out: open_output(output)?,
+            }),
+            ExportFormat::Graphml => Box::new(GraphmlWriter::new(open_output(output)?, &name)?),
+            ExportFormat::Dot => Box::new(DotWriter::new(open_output(output)?, &name)?),
+        };
+
+        let txn = self.read_txn()?;
+
+        log::info!("exporting pages");
+        let mut included = PageSet::default();
+        let mut page_count = 0;
+        let mut redirect_count = 0;
+        for entry in self.iter_pages(&txn)? {
+            let (page, in_degree, out_degree) = entry?;
+            if filter.includes(self, &txn, page, in_degree, out_degree)? {
+                let title = self.get_title(&txn, page)?;
+                writer.node(page, title.as_deref(), false)?;
+                included.insert(page);
+                page_count += 1;
+            }
+        }
+        for entry in self.iter_redirects(&txn)? {
+            let (source, target) = entry?;
+
+            // Targets without links were not exported yet.
+            if !included.contains(target)
+                && !self.has_links(&txn, target)?
+                && filter.includes(self, &txn, target, 0, 0)?
+            {
+                let title = self.get_title(&txn, target)?;
+                writer.node(target, title.as_deref(), false)?;
+                included.insert(target);
+                page_count += 1;
+            }
+
+            // Sources with links were decided on with the other pages already.
+            if included.contains(target)
+                && !included.contains(source)
+                && !self.has_links(&txn, source)?
+                && filter.includes_page(self, &txn, source)?
+            {
+                let title = self.get_title(&txn, source)?;
+                writer.node(source, title.as_deref(), true)?;
+                included.insert(source);
+                redirect_count += 1;
+            }
+        }
+
+        log::info!("exporting links");
+        let mut link_count = 0;
+        for entry in self.iter_outgoing(&txn)? {
+            let (source, targets) = entry?;
+            if !included.contains(source) {
+                continue;
+            }
+            for target in targets {
+                if included.contains(target) {
+                    writer.link(source, target)?;
+                    link_count += 1;
+                }
+            }
+        }
+        for entry in self.iter_redirects(&txn)? {
+            let (source, target) = entry?;
+            if included.contains(source) && included.contains(target) {
+                writer.redirect(source, target)?;
+            }
+        }
+
+        txn.commit()?;
+        writer.finish()?;
+
+        log::info!(
+            "exported {page_count} pages, {redirect_count} redirects and {link_count} links from '{name}'"
+        );
+        Ok(())
+    }
+}
+
+/// Open a buffered writer to a file, or to stdout if no path is given.
+fn open_output(path: Option<&Path>) -> Result<Box<dyn Write>> {
+    Ok(match path {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    })
+}
+
+/// Replace characters that would break the line-based TSV format.
+fn escape_tsv(s: &str) -> String {
+    s.replace(['\t', '\n', '\r'], " ")
+}
+
+/// Escape a string for use in XML text and attribute values.
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Escape a string for use in a quoted DOT identifier.
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+struct TsvWriter {
+    out: Box<dyn Write>,
+}
+
+impl GraphWriter for TsvWriter {
+    fn node(&mut self, page: PageId, title: Option<&str>, _redirect: bool) -> Result<()> {
+        if let Some(title) = title {
+            writeln!(self.out, "title\t{page}\t{}", escape_tsv(title))?;
+        }
+        Ok(())
+    }
+
+    fn link(&mut self, source: PageId, target: PageId) -> Result<()> {
+        writeln!(self.out, "link\t{source}\t{target}")?;
+        Ok(())
+    }
+
+    fn redirect(&mut self, source: PageId, target: PageId) -> Result<()> {
+        writeln!(self.out, "redirect\t{source}\t{target}")?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+struct GraphmlWriter {
+    out: Box<dyn Write>,
+}
+
+impl GraphmlWriter {
+    fn new(mut out: Box<dyn Write>, name: &str) -> Result<Self> {
+        writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            out,
+            r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+        )?;
+        writeln!(
+            out,
+            r#"  <key id="title" for="node" attr.name="title" attr.type="string"/>"#
+        )?;
+        writeln!(
+            out,
+            r#"  <key id="redirect" for="node" attr.name="redirect" attr.type="boolean"/>"#
+        )?;
+        writeln!(
+            out,
+            r#"  <key id="kind" for="edge" attr.name="kind" attr.type="string"/>"#
+        )?;
+        writeln!(
+            out,
+            r#"  <graph id="{}" edgedefault="directed">"#,
+            escape_xml(name)
+        )?;
+        Ok(Self { out })
+    }
+}
+
+impl GraphWriter for GraphmlWriter {
+    fn node(&mut self, page: PageId, title: Option<&str>, redirect: bool) -> Result<()> {
+        write!(self.out, r#"    <node id="{page}">"#)?;
+        if let Some(title) = title {
+            write!(
+                self.out,
+                r#"<data key="title">{}</data>"#,
+                escape_xml(title)
+            )?;
+        }
+        if redirect {
+            write!(self.out, r#"<data key="redirect">true</data>"#)?;
+        }
+        writeln!(self.out, "</node>")?;
+        Ok(())
+    }
+
+    fn link(&mut self, source: PageId, target: PageId) -> Result<()> {
+        writeln!(
+            self.out,
+            r#"    <edge source="{source}" target="{target}"><data key="kind">link</data></edge>"#
+        )?;
+        Ok(())
+    }
+
+    fn redirect(&mut self, source: PageId, target: PageId) -> Result<()> {
+        writeln!(
+            self.out,
+            r#"    <edge source="{source}" target="{target}"><data key="kind">redirect</data></edge>"#
+        )?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        writeln!(self.out, "  </graph>")?;
+        writeln!(self.out, "</graphml>")?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+struct DotWriter {
+    out: Box<dyn Write>,
+}
+
+impl DotWriter {
+    fn new(mut out: Box<dyn Write>, name: &str) -> Result<Self> {
+        writeln!(out, "digraph \"{}\" {{", escape_dot(name))?;
+        Ok(Self { out })
+    }
+}
+
+impl GraphWriter for DotWriter {
+    fn node(&mut self, page: PageId, title: Option<&str>, redirect: bool) -> Result<()> {
+        let mut attributes = Vec::new();
+        if let Some(title) = title {
+            attributes.push(format!("label=\"{}\"", escape_dot(title)));
+        }
+        if redirect {
+            attributes.push("style=dashed".to_string());
+        }
+        if attributes.is_empty() {
+            writeln!(self.out, "  {page};")?;
+        } else {
+            writeln!(self.out, "  {page} [{}];", attributes.join(", "))?;
+        }
+        Ok(())
+    }
+
+    fn link(&mut self, source: PageId, target: PageId) -> Result<()> {
+        writeln!(self.out, "  {source} -> {target};")?;
+        Ok(())
+    }
+
+    fn redirect(&mut self, source: PageId, target: PageId) -> Result<()> {
+        writeln!(self.out, "  {source} -> {target} [style=dashed];")?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        writeln!(self.out, "}}")?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+struct Neo4jCsvWriter {
+    nodes: csv::Writer<File>,
+    relationships: csv::Writer<File>,
+}
+
+impl Neo4jCsvWriter {
+    fn new(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        let mut nodes = csv::Writer::from_path(dir.join("nodes.csv"))?;
+        nodes.write_record(["pageId:ID", "title", "redirect:boolean", ":LABEL"])?;
+        let mut relationships = csv::Writer::from_path(dir.join("relationships.csv"))?;
+        relationships.write_record([":START_ID", ":END_ID", ":TYPE"])?;
+        Ok(Self {
+            nodes,
+            relationships,
+        })
+    }
+}
+
+impl GraphWriter for Neo4jCsvWriter {
+    fn node(&mut self, page: PageId, title: Option<&str>, redirect: bool) -> Result<()> {
+        self.nodes.write_record([
+            page.to_string().as_str(),
+            title.unwrap_or(""),
+            if redirect { "true" } else { "false" },
+            "Page",
+        ])?;
+        Ok(())
+    }
+
+    fn link(&mut self, source: PageId, target: PageId) -> Result<()> {
+        self.relationships.write_record([
+            source.to_string().as_str(),
+            target.to_string().as_str(),
+            "LINKS_TO",
+        ])?;
+        Ok(())
+    }
+
+    fn redirect(&mut self, source: PageId, target: PageId) -> Result<()> {
+        self.relationships.write_record([
+            source.to_string().as_str(),
+            target.to_string().as_str(),
+            "REDIRECTS_TO",
+        ])?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.nodes.flush()?;
+        self.relationships.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn within_respects_optional_bounds() {
+        assert!(within(5, None, None));
+        assert!(within(5, Some(5), Some(5)));
+        assert!(!within(4, Some(5), None));
+        assert!(!within(6, None, Some(5)));
+    }
+
+    #[test]
+    fn escapes_special_characters() {
+        assert_eq!(escape_tsv("a\tb\nc"), "a b c");
+        assert_eq!(escape_xml(r#"<a & "b">"#), "&lt;a &amp; &quot;b&quot;&gt;");
+        assert_eq!(escape_dot(r#"say "hi" \o/"#), r#"say \"hi\" \\o/"#);
+    }
+}
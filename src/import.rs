@@ -0,0 +1,457 @@
+use crate::{
+    database::{Database, Metadata, Mode, PageId},
+    parse::{
+        compress_redirect_chains, open_decompressed, store_redirect, ExtractMatch, ParseSkipReason,
+        ParseStats,
+    },
+};
+use anyhow::{anyhow, Context, Result};
+use csv::ByteRecord;
+use humantime::format_duration;
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    fs::{self, File},
+    io::{BufReader, BufWriter, ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+/// Number of edges that are sorted in memory before being written to disk as a sorted run.
+pub const RUN_SIZE_EDGES: usize = 16_000_000;
+
+/// Number of entries inserted into the database before committing the write transaction.
+const MAX_TXN_SIZE: usize = 4_000_000;
+
+/// Struct to hold paths to the files of a generic graph to import. All files contain one record
+/// per line with delimited columns, and may be compressed. Lines starting with `#` are ignored.
+#[derive(Debug)]
+pub struct ImportFiles {
+    /// Edges as `source<delimiter>target` page id pairs.
+    pub edges: PathBuf,
+    /// Optional aliases as `alias<delimiter>canonical` page id pairs, stored as redirects.
+    pub aliases: Option<PathBuf>,
+    /// Optional labels as `id<delimiter>label` pairs, stored as page titles.
+    pub labels: Option<PathBuf>,
+    /// Column delimiter. If not given, it is a comma for `.csv` files and a tab otherwise.
+    pub delimiter: Option<u8>,
+}
+
+impl ImportFiles {
+    fn delimiter_for(&self, path: &Path) -> u8 {
+        self.delimiter.unwrap_or_else(|| {
+            let is_csv = path
+                .file_name()
+                .and_then(|s| s.to_str())
+                .is_some_and(|s| s.contains(".csv"));
+            if is_csv {
+                b','
+            } else {
+                b'\t'
+            }
+        })
+    }
+}
+
+impl Database {
+    /// Import a database from a generic graph in the form of edge lists. The database will be built
+    /// in the specified temporary path and then copied to the final path, in the same way as when
+    /// building from dumps. Edges are sorted externally, so the edge list may exceed memory.
+    pub fn import(
+        metadata: &Metadata,
+        files: &ImportFiles,
+        tmp_path: &Path,
+        final_path: &Path,
+    ) -> Result<()> {
+        Self::import_with_run_size(metadata, files, tmp_path, final_path, RUN_SIZE_EDGES)
+    }
+
+    #[allow(clippy::too_many_lines)]
+    fn import_with_run_size(
+        metadata: &Metadata,
+        files: &ImportFiles,
+        tmp_path: &Path,
+        final_path: &Path,
+        run_size: usize,
+    ) -> Result<()> {
+        let start = Instant::now();
+
+        if tmp_path.exists() {
+            return Err(anyhow!(
+                "temporary database path '{}' already exists",
+                tmp_path.display()
+            ));
+        }
+
+        log::info!("creating new database");
+        fs::create_dir_all(tmp_path)?;
+
+        let db = Database::open(tmp_path, Mode::Build)?;
+
+        let mut redirects = HashMap::new();
+        if let Some(aliases) = &files.aliases {
+            log::info!("reading aliases");
+            redirects = read_records(
+                aliases,
+                files.delimiter_for(aliases),
+                |record| -> Result<ExtractMatch<(PageId, PageId)>> {
+                    let alias = parse_id(record, 0)?;
+                    let canonical = parse_id(record, 1)?;
+                    if alias == canonical {
+                        return Ok(ExtractMatch::Skip(ParseSkipReason::SelfRedirect));
+                    }
+                    Ok(ExtractMatch::Store((alias, canonical)))
+                },
+                store_redirect,
+            )?;
+            log::info!("{} aliases found!", redirects.len());
+
+            log::info!("compressing alias chains");
+            compress_redirect_chains(&mut redirects);
+
+            log::info!("inserting aliases into database as redirects");
+            let mut txn = db.write_txn()?;
+            for (source, target) in &redirects {
+                db.insert_redirect(&mut txn, *source, *target)?;
+            }
+            txn.commit()?;
+        }
+
+        if let Some(labels) = &files.labels {
+            log::info!("reading labels & inserting into database");
+            let insert_labels = |labels: &mut Vec<(PageId, String)>| -> Result<()> {
+                let mut txn = db.write_txn()?;
+                for (id, label) in labels.drain(..) {
+                    db.insert_title(&mut txn, id, &label)?;
+                }
+                txn.commit()?;
+                Ok(())
+            };
+            let mut remaining = read_records(
+                labels,
+                files.delimiter_for(labels),
+                |record| -> Result<ExtractMatch<(PageId, String)>> {
+                    let id = parse_id(record, 0)?;
+                    let label = record.get(1).context("missing label column")?;
+                    Ok(ExtractMatch::Store((
+                        id,
+                        std::str::from_utf8(label)?.to_string(),
+                    )))
+                },
+                |batch: &mut Vec<(PageId, String)>, label| {
+                    batch.push(label);
+                    if batch.len() >= MAX_TXN_SIZE {
+                        insert_labels(batch)?;
+                    }
+                    Ok(())
+                },
+            )?;
+            insert_labels(&mut remaining)?;
+        }
+
+        log::info!("sorting edges into runs");
+        let runs_dir = tmp_path.join("runs");
+        fs::create_dir_all(&runs_dir)?;
+        let mut runs = read_records(
+            &files.edges,
+            files.delimiter_for(&files.edges),
+            |record| -> Result<ExtractMatch<(PageId, PageId)>> {
+                let source = parse_id(record, 0)?;
+                let target = parse_id(record, 1)?;
+
+                let source = *redirects.get(&source).unwrap_or(&source);
+                let target = *redirects.get(&target).unwrap_or(&target);
+
+                if source == target {
+                    return Ok(ExtractMatch::Skip(ParseSkipReason::SelfLink));
+                }
+
+                Ok(ExtractMatch::Store((target, source)))
+            },
+            |runs: &mut SortedRuns, edge| runs.push(edge, &runs_dir, run_size),
+        )?;
+        runs.write_run(&runs_dir)?;
+
+        log::info!(
+            "merging {} sorted runs & inserting links into database",
+            runs.paths.len()
+        );
+        let link_count = insert_sorted_runs(&db, &runs.paths)?;
+        fs::remove_dir_all(&runs_dir)?;
+        if link_count == 0 {
+            return Err(anyhow!(
+                "nothing parsed from edge list '{}'",
+                files.edges.display()
+            ));
+        }
+        log::info!("{link_count} links found!");
+
+        log::info!("generating outgoing table");
+        let mut txn = db.write_txn()?;
+        db.generate_outgoing_table(&mut txn)?;
+        txn.commit()?;
+
+        log::info!("copying database to final path");
+        db.copy_to_serve(final_path)?;
+
+        log::info!(
+            "database '{}' succesfully imported in {}!",
+            metadata.to_name(),
+            format_duration(start.elapsed())
+        );
+
+        Ok(())
+    }
+}
+
+/// Read a delimited file record by record, extracting and storing each record in the same way
+/// dump files are parsed.
+fn read_records<
+    F: Fn(&ByteRecord) -> Result<ExtractMatch<T>>,
+    G: Fn(&mut U, T) -> Result<()>,
+    T,
+    U: Default,
+>(
+    path: &Path,
+    delimiter: u8,
+    extract_record: F,
+    store_record: G,
+) -> Result<U> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .comment(Some(b'#'))
+        .trim(csv::Trim::All)
+        .from_reader(open_decompressed(path)?);
+
+    let mut record = ByteRecord::new();
+    let mut result = U::default();
+    let mut stats = ParseStats::default();
+
+    while reader.read_byte_record(&mut record)? {
+        stats.matches_total += 1;
+        stats.process(&mut result, extract_record(&record), &store_record)?;
+    }
+
+    stats.log(&path.display().to_string());
+
+    Ok(result)
+}
+
+/// Parse the page id in a column of a record.
+fn parse_id(record: &ByteRecord, column: usize) -> Result<PageId> {
+    let field = record
+        .get(column)
+        .context(format!("missing column {column}"))?;
+    Ok(std::str::from_utf8(field)?.parse::<PageId>()?)
+}
+
+/// Edges as pairs of page ids, sorted in memory and written to disk in runs. Edges are imported in
+/// the incoming form (target, source), so they are merged by target.
+#[derive(Debug, Default)]
+pub struct SortedRuns {
+    edges: Vec<(PageId, PageId)>,
+    pub paths: Vec<PathBuf>,
+}
+
+impl SortedRuns {
+    /// Add an edge, writing the edges in memory to a new run file once there are `run_size`.
+    pub fn push(&mut self, edge: (PageId, PageId), dir: &Path, run_size: usize) -> Result<()> {
+        self.edges.push(edge);
+        if self.edges.len() >= run_size {
+            self.write_run(dir)?;
+        }
+        Ok(())
+    }
+
+    /// Sort and deduplicate the edges in memory and write them to a new run file in the directory.
+    pub fn write_run(&mut self, dir: &Path) -> Result<()> {
+        if self.edges.is_empty() {
+            return Ok(());
+        }
+
+        self.edges.sort_unstable();
+        self.edges.dedup();
+
+        let path = dir.join(format!("run-{}", self.paths.len()));
+        log::debug!(
+            "writing run of {} edges to '{}'",
+            self.edges.len(),
+            path.display()
+        );
+
+        let mut writer = BufWriter::new(File::create(&path)?);
+        for (first, second) in self.edges.drain(..) {
+            writer.write_all(&first.to_le_bytes())?;
+            writer.write_all(&second.to_le_bytes())?;
+        }
+        writer.flush()?;
+
+        self.paths.push(path);
+        Ok(())
+    }
+}
+
+/// Reader of the edges in a run file, in the order they were written.
+struct RunReader {
+    reader: BufReader<File>,
+}
+
+impl RunReader {
+    fn open(path: &Path) -> Result<Self> {
+        Ok(Self {
+            reader: BufReader::new(File::open(path)?),
+        })
+    }
+
+    fn next_edge(&mut self) -> Result<Option<(PageId, PageId)>> {
+        let mut buffer = [0; 8];
+        match self.reader.read_exact(&mut buffer) {
+            Ok(()) => {
+                let (first, second) = buffer.split_at(4);
+                Ok(Some((
+                    PageId::from_le_bytes(first.try_into()?),
+                    PageId::from_le_bytes(second.try_into()?),
+                )))
+            }
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// The edges of sorted runs merged into a single sorted stream, without duplicates.
+pub struct MergedRuns {
+    readers: Vec<RunReader>,
+    heap: BinaryHeap<Reverse<((PageId, PageId), usize)>>,
+    last: Option<(PageId, PageId)>,
+}
+
+impl MergedRuns {
+    pub fn open(paths: &[PathBuf]) -> Result<Self> {
+        let mut readers = paths
+            .iter()
+            .map(|path| RunReader::open(path))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut heap = BinaryHeap::new();
+        for (i, reader) in readers.iter_mut().enumerate() {
+            if let Some(edge) = reader.next_edge()? {
+                heap.push(Reverse((edge, i)));
+            }
+        }
+
+        Ok(Self {
+            readers,
+            heap,
+            last: None,
+        })
+    }
+
+    pub fn next_edge(&mut self) -> Result<Option<(PageId, PageId)>> {
+        while let Some(Reverse((edge, i))) = self.heap.pop() {
+            if let Some(next) = self.readers[i].next_edge()? {
+                self.heap.push(Reverse((next, i)));
+            }
+
+            // Duplicates across runs are adjacent, since the runs are sorted.
+            if self.last != Some(edge) {
+                self.last = Some(edge);
+                return Ok(Some(edge));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Merge sorted runs of edges and insert them into the database as incoming links. Since the
+/// merged edges are sorted by target, every target is inserted exactly once. Returns the number
+/// of unique links inserted.
+fn insert_sorted_runs(db: &Database, paths: &[PathBuf]) -> Result<usize> {
+    let mut edges = MergedRuns::open(paths)?;
+
+    let mut txn = db.write_txn()?;
+    let mut txn_size = 0;
+    let mut link_count = 0;
+    let mut current: Option<(PageId, Vec<PageId>)> = None;
+
+    while let Some((target, source)) = edges.next_edge()? {
+        match &mut current {
+            Some((current_target, sources)) if *current_target == target => sources.push(source),
+            _ => {
+                if let Some((current_target, sources)) = current.take() {
+                    link_count += sources.len();
+                    txn_size += sources.len();
+                    db.insert_links_incoming(&mut txn, current_target, sources)?;
+                    if txn_size >= MAX_TXN_SIZE {
+                        txn.commit()?;
+                        txn = db.write_txn()?;
+                        txn_size = 0;
+                    }
+                }
+                current = Some((target, vec![source]));
+            }
+        }
+    }
+
+    if let Some((current_target, sources)) = current {
+        link_count += sources.len();
+        db.insert_links_incoming(&mut txn, current_target, sources)?;
+    }
+    txn.commit()?;
+
+    Ok(link_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn import_merges_runs_and_resolves_aliases() {
+        let dir = std::env::temp_dir().join(format!("wikipath-import-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let edges = dir.join("edges.tsv");
+        fs::write(
+            &edges,
+            "# source\ttarget\n1\t2\n2\t3\n1\t3\n4\t1\n3\t1\n1\t2\n2\t5\n5\t5\nsource\ttarget\n",
+        )
+        .unwrap();
+        let aliases = dir.join("aliases.tsv");
+        fs::write(&aliases, "5\t4\n").unwrap();
+        let labels = dir.join("labels.csv");
+        fs::write(&labels, "1,One\n2,\"Two, Too\"\n").unwrap();
+
+        let files = ImportFiles {
+            edges,
+            aliases: Some(aliases),
+            labels: Some(labels),
+            delimiter: None,
+        };
+        let metadata = Metadata::new("graph", "20260101").unwrap();
+        let tmp_path = dir.join("tmp").join(metadata.to_name());
+        let final_path = dir.join(metadata.to_name());
+        Database::import_with_run_size(&metadata, &files, &tmp_path, &final_path, 2).unwrap();
+        assert!(!tmp_path.exists());
+
+        let db = Database::open(&final_path, Mode::Serve).unwrap();
+        let txn = db.read_txn().unwrap();
+        assert_eq!(db.get_outgoing_links(&txn, 1).unwrap(), vec![2, 3]);
+        assert_eq!(db.get_outgoing_links(&txn, 2).unwrap(), vec![3, 4]);
+        assert_eq!(db.get_outgoing_links(&txn, 4).unwrap(), vec![1]);
+        assert_eq!(db.get_incoming_links(&txn, 1).unwrap(), vec![3, 4]);
+        assert_eq!(
+            db.get_incoming_links(&txn, 5).unwrap(),
+            Vec::<PageId>::new()
+        );
+        assert_eq!(db.get_redirect(&txn, 5).unwrap(), Some(4));
+        assert_eq!(db.get_title(&txn, 2).unwrap().as_deref(), Some("Two, Too"));
+        assert_eq!(db.get_title(&txn, 3).unwrap(), None);
+        txn.commit().unwrap();
+        drop(db);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,225 @@
+use crate::{
+    database::{Database, Metadata, Mode, PageId, TableLengths},
+    misc::{format_bytes, TopPages},
+};
+use anyhow::Result;
+use serde::Serialize;
+use std::{fmt, path::Path};
+
+/// Statistics about a database and the graph it contains. The page statistics only cover linked
+/// pages, i.e. pages with at least one incoming or outgoing link, since the database does not
+/// store pages without links.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DatabaseInfo {
+    metadata: Metadata,
+    file_size: u64,
+    tables: TableLengths,
+    linked_pages: u64,
+    links: u64,
+    redirects: u64,
+    linked_dead_ends: u64, // linked pages without outgoing links
+    linked_orphans: u64,   // linked pages without incoming links
+    in_degrees: Vec<DegreeBucket>,
+    out_degrees: Vec<DegreeBucket>,
+    most_linked: Vec<TopPage>,
+    most_linking: Vec<TopPage>,
+}
+
+/// The number of pages with a degree in the (inclusive) range `min..=max`.
+#[derive(Debug, Serialize)]
+pub struct DegreeBucket {
+    min: usize,
+    max: usize,
+    count: u64,
+}
+
+/// A page with its degree, and title if the database contains titles.
+#[derive(Debug, Serialize)]
+pub struct TopPage {
+    page: PageId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    degree: usize,
+}
+
+/// A histogram of degrees, with power-of-two sized buckets: 0, 1, 2-3, 4-7, etc.
+#[derive(Debug, Default)]
+struct DegreeHistogram(Vec<u64>);
+
+impl DegreeHistogram {
+    fn add(&mut self, degree: usize) {
+        let bucket = (usize::BITS - degree.leading_zeros()) as usize;
+        if self.0.len() <= bucket {
+            self.0.resize(bucket + 1, 0);
+        }
+        self.0[bucket] += 1;
+    }
+
+    fn into_buckets(self) -> Vec<DegreeBucket> {
+        self.0
+            .into_iter()
+            .enumerate()
+            .map(|(i, count)| DegreeBucket {
+                min: if i == 0 { 0 } else { 1 << (i - 1) },
+                max: (1 << i) - 1,
+                count,
+            })
+            .collect()
+    }
+}
+
+impl Database {
+    /// Collect statistics about the database at a path, including the `top` pages with the most
+    /// incoming and outgoing links. Streams over the tables, so memory use is bounded.
+    pub fn info(path: &Path, top: usize) -> Result<DatabaseInfo> {
+        let db = Database::open(path, Mode::Serve)?;
+        let file_size = std::fs::metadata(path)?.len();
+
+        log::info!("collecting statistics");
+        let txn = db.read_txn()?;
+
+        let mut linked_pages = 0;
+        let mut links = 0;
+        let mut linked_dead_ends = 0;
+        let mut linked_orphans = 0;
+        let mut in_degrees = DegreeHistogram::default();
+        let mut out_degrees = DegreeHistogram::default();
+        let mut most_linked = TopPages::new(top);
+        let mut most_linking = TopPages::new(top);
+        for entry in db.iter_pages(&txn)? {
+            let (page, in_degree, out_degree) = entry?;
+            linked_pages += 1;
+            links += out_degree as u64;
+            if in_degree == 0 {
+                linked_orphans += 1;
+            }
+            if out_degree == 0 {
+                linked_dead_ends += 1;
+            }
+            in_degrees.add(in_degree);
+            out_degrees.add(out_degree);
+            most_linked.add(page, in_degree, ());
+            most_linking.add(page, out_degree, ());
+        }
+
+        let top_pages = |top: TopPages<()>| -> Result<Vec<TopPage>> {
+            top.into_pages()
+                .into_iter()
+                .map(|(page, degree, ())| {
+                    Ok(TopPage {
+                        page,
+                        title: db.get_title(&txn, page)?,
+                        degree,
+                    })
+                })
+                .collect()
+        };
+        let most_linked = top_pages(most_linked)?;
+        let most_linking = top_pages(most_linking)?;
+
+        let tables = db.table_lengths(&txn)?;
+        txn.commit()?;
+
+        Ok(DatabaseInfo {
+            metadata: db.metadata,
+            file_size,
+            redirects: tables.redirects,
+            tables,
+            linked_pages,
+            links,
+            linked_dead_ends,
+            linked_orphans,
+            in_degrees: in_degrees.into_buckets(),
+            out_degrees: out_degrees.into_buckets(),
+            most_linked,
+            most_linking,
+        })
+    }
+}
+
+impl fmt::Display for DatabaseInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let optional = |len: Option<u64>| len.map_or("absent".to_string(), |len| len.to_string());
+
+        writeln!(f, "database:   {}", self.metadata.to_name())?;
+        writeln!(f, "language:   {}", self.metadata.language_code)?;
+        writeln!(f, "date:       {}", self.metadata.date_code)?;
+        writeln!(f, "file size:  {}", format_bytes(self.file_size))?;
+        writeln!(f)?;
+        writeln!(f, "tables:")?;
+        writeln!(f, "  redirects:  {}", self.tables.redirects)?;
+        writeln!(f, "  incoming:   {}", self.tables.incoming)?;
+        writeln!(f, "  outgoing:   {}", self.tables.outgoing)?;
+        writeln!(f, "  titles:     {}", optional(self.tables.titles))?;
+        writeln!(f, "  namespaces: {}", optional(self.tables.namespaces))?;
+        writeln!(f)?;
+        writeln!(f, "linked pages: {}", self.linked_pages)?;
+        writeln!(f, "links:        {}", self.links)?;
+        writeln!(f, "redirects:    {}", self.redirects)?;
+        writeln!(
+            f,
+            "dead ends:    {} (linked pages without outgoing links)",
+            self.linked_dead_ends
+        )?;
+        writeln!(
+            f,
+            "orphans:      {} (linked pages without incoming links)",
+            self.linked_orphans
+        )?;
+
+        for (name, buckets) in [
+            ("in-degree", &self.in_degrees),
+            ("out-degree", &self.out_degrees),
+        ] {
+            writeln!(f)?;
+            writeln!(f, "{name} distribution:")?;
+            for bucket in buckets {
+                let range = if bucket.min == bucket.max {
+                    bucket.min.to_string()
+                } else {
+                    format!("{}-{}", bucket.min, bucket.max)
+                };
+                writeln!(f, "  {range:>15}: {}", bucket.count)?;
+            }
+        }
+
+        for (name, pages) in [
+            ("most linked pages", &self.most_linked),
+            ("most linking pages", &self.most_linking),
+        ] {
+            writeln!(f)?;
+            writeln!(f, "{name}:")?;
+            for page in pages {
+                match &page.title {
+                    Some(title) => writeln!(f, "  {:>10}  {} ({title})", page.degree, page.page)?,
+                    None => writeln!(f, "  {:>10}  {}", page.degree, page.page)?,
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_uses_power_of_two_buckets() {
+        let mut histogram = DegreeHistogram::default();
+        for degree in [0, 1, 2, 3, 4, 7, 8] {
+            histogram.add(degree);
+        }
+        let buckets = histogram.into_buckets();
+        let counts = buckets
+            .iter()
+            .map(|b| (b.min, b.max, b.count))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            counts,
+            vec![(0, 0, 1), (1, 1, 1), (2, 3, 2), (4, 7, 2), (8, 15, 1)]
+        );
+    }
+}
@@ -0,0 +1,141 @@
+use crate::database::Metadata;
+use anyhow::{Context, Result};
+use std::{
+    fmt,
+    fs::{self, File, OpenOptions, TryLockError},
+    io::{Read, Seek, Write},
+    path::{Path, PathBuf},
+};
+
+/// An advisory lock on building a database, held in a lock file per directory involved in the
+/// build. The locks are released by the operating system when the holding process exits, so a
+/// crashed build never blocks later builds. The lock files contain the PID of the holder, which is
+/// cleared on release, so a PID in an unlocked file means a previous build did not exit cleanly. The
+/// files themselves are left in place, since removing them could let two builds lock different files.
+#[derive(Debug)]
+pub struct BuildLock {
+    files: Vec<File>,
+}
+
+impl BuildLock {
+    /// Acquire the build lock of a database in each of the given directories. Returns an error
+    /// naming the PID of the holder if another process is building the same database.
+    pub fn acquire(metadata: &Metadata, dirs: &[&Path]) -> Result<Self> {
+        let mut lock = Self { files: Vec::new() };
+        for dir in dirs {
+            lock.files.push(lock_file(metadata, dir)?); // on error, acquired locks are released
+        }
+        Ok(lock)
+    }
+
+    /// Acquire the build lock of a database in a single directory, or return `None` if another
+    /// process is building the database, e.g. before removing its files.
+    pub fn try_acquire(metadata: &Metadata, dir: &Path) -> Result<Option<Self>> {
+        match lock_file(metadata, dir) {
+            Ok(file) => Ok(Some(Self { files: vec![file] })),
+            Err(e) if e.is::<LockHeld>() => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// The error of a build lock that is held by another process.
+#[derive(Debug)]
+struct LockHeld {
+    name: String,
+    holder: String,
+    path: PathBuf,
+}
+
+impl fmt::Display for LockHeld {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "database '{}' is already being built by process {} (lock file '{}')",
+            self.name,
+            self.holder,
+            self.path.display()
+        )
+    }
+}
+
+impl std::error::Error for LockHeld {}
+
+impl Drop for BuildLock {
+    fn drop(&mut self) {
+        for file in &self.files {
+            if let Err(e) = file.set_len(0) {
+                log::warn!("failed to clear build lock file: {e}");
+            }
+        }
+    }
+}
+
+fn lock_path(metadata: &Metadata, dir: &Path) -> PathBuf {
+    dir.join(format!(".{}.lock", metadata.to_name()))
+}
+
+fn lock_file(metadata: &Metadata, dir: &Path) -> Result<File> {
+    fs::create_dir_all(dir)?;
+    let path = lock_path(metadata, dir);
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .with_context(|| format!("failed to open lock file '{}'", path.display()))?;
+
+    let mut holder = String::new();
+    file.read_to_string(&mut holder)?;
+    let holder = holder.trim();
+
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => {
+            let holder = if holder.is_empty() { "unknown" } else { holder };
+            return Err(LockHeld {
+                name: metadata.to_name(),
+                holder: holder.to_string(),
+                path,
+            }
+            .into());
+        }
+        Err(TryLockError::Error(e)) => {
+            return Err(e).with_context(|| format!("failed to lock '{}'", path.display()));
+        }
+    }
+
+    if !holder.is_empty() {
+        log::warn!(
+            "found stale lock of process {holder} that is no longer running, taking over '{}'",
+            path.display()
+        );
+    }
+
+    file.set_len(0)?;
+    file.rewind()?;
+    writeln!(file, "{}", std::process::id())?;
+    file.sync_all()?;
+
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn try_acquire_skips_held_locks() {
+        let dir = std::env::temp_dir().join(format!("wikipath-lock-{}", std::process::id()));
+        let metadata = Metadata::new("lock", "20260101").unwrap();
+
+        let lock = BuildLock::acquire(&metadata, &[&dir]).unwrap();
+        assert!(BuildLock::try_acquire(&metadata, &dir).unwrap().is_none());
+        assert!(BuildLock::acquire(&metadata, &[&dir]).is_err());
+        drop(lock);
+        assert!(BuildLock::try_acquire(&metadata, &dir).unwrap().is_some());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
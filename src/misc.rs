@@ -1,12 +1,31 @@
-use crate::database::{Database, Metadata};
-use anyhow::Result;
-use std::{fs, path::Path};
+use crate::{
+    database::{Database, Metadata, PageId},
+    lock::BuildLock,
+};
+use anyhow::{bail, Context, Result};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashSet},
+    fs,
+    path::Path,
+    time::SystemTime,
+};
+
+/// The largest number of words (16 MiB) a bitset of pages grows to while it contains fewer pages
+/// than words, covering page ids up to about 134 million.
+const MAX_SPARSE_BITSET_WORDS: usize = 1 << 21;
 
 /// Remove databases with different date but the same language code as the given anchor.
 /// This function will remove all databases in the given directory that have the same language code
 /// as the anchor but a different date code. This is useful when building a new database, as the
 /// old databases with the same language code but different date code are no longer needed.
-pub fn remove_different_date_databases(anchor: &Metadata, dir: &Path) -> Result<()> {
+/// Databases that are still being built according to their build lock in the lock directory are
+/// skipped.
+pub fn remove_different_date_databases(
+    anchor: &Metadata,
+    dir: &Path,
+    lock_dir: &Path,
+) -> Result<()> {
     log::debug!(
         "removing databases with different date in '{}'",
         dir.display()
@@ -18,6 +37,13 @@ pub fn remove_different_date_databases(anchor: &Metadata, dir: &Path) -> Result<
         match Database::get_metadata(&path) {
             Ok(md) => {
                 if md.language_code == anchor.language_code && md.date_code != anchor.date_code {
+                    let Some(_lock) = BuildLock::try_acquire(&md, lock_dir)? else {
+                        log::warn!(
+                            "skipping database with different date '{}' that is being built",
+                            path.display()
+                        );
+                        continue;
+                    };
                     if path.is_dir() {
                         fs::remove_dir_all(&path)?;
                     } else {
@@ -34,3 +60,206 @@ pub fn remove_different_date_databases(anchor: &Metadata, dir: &Path) -> Result<
 
     Ok(())
 }
+
+/// Get the current (UTC) date as a date code, e.g. "20260401".
+pub fn current_date_code() -> String {
+    humantime::format_rfc3339(SystemTime::now()).to_string()[..10].replace('-', "")
+}
+
+/// Format a number of bytes as a human-readable string using binary prefixes, e.g. "1.50 GiB".
+#[allow(clippy::cast_precision_loss)]
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.2} {}", UNITS[unit])
+    }
+}
+
+/// Parse a human-readable number of bytes with an optional binary suffix, e.g. "512K" or "10MiB".
+/// The suffix is case-insensitive and may be given as "k", "kb" or "kib" (and likewise for m/g).
+pub fn parse_bytes(s: &str) -> Result<u64> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, suffix) = s.split_at(split);
+    let number = number
+        .parse::<u64>()
+        .with_context(|| format!("invalid byte size '{s}'"))?;
+    let multiplier: u64 = match suffix.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1024,
+        "m" | "mb" | "mib" => 1024 * 1024,
+        "g" | "gb" | "gib" => 1024 * 1024 * 1024,
+        _ => bail!("invalid byte size suffix in '{s}'"),
+    };
+    number
+        .checked_mul(multiplier)
+        .with_context(|| format!("byte size '{s}' is too large"))
+}
+
+/// The pages with the highest scores (e.g. degrees), along with some data for each page. Keeps at
+/// most a fixed number of pages in memory.
+pub struct TopPages<T> {
+    size: usize,
+    heap: BinaryHeap<Reverse<(usize, Reverse<PageId>, T)>>,
+}
+
+impl<T: Ord> TopPages<T> {
+    pub fn new(size: usize) -> Self {
+        Self {
+            size,
+            heap: BinaryHeap::with_capacity(size + 1),
+        }
+    }
+
+    pub fn add(&mut self, page: PageId, score: usize, data: T) {
+        self.heap.push(Reverse((score, Reverse(page), data)));
+        if self.heap.len() > self.size {
+            self.heap.pop();
+        }
+    }
+
+    /// Get the pages ordered by descending score (and ascending page id for equal scores).
+    pub fn into_pages(self) -> Vec<(PageId, usize, T)> {
+        self.heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse((score, Reverse(page), data))| (page, score, data))
+            .collect()
+    }
+}
+
+/// A set of pages, stored as a bitset indexed by page id. Page ids are dense, so this takes far
+/// less memory per visited page than a hash set. The bitset grows as larger ids are inserted, up to
+/// `MAX_SPARSE_BITSET_WORDS` words, beyond which a hash set is used if the ids turn out to be
+/// sparse (e.g. in imported databases).
+#[derive(Debug)]
+pub enum PageSet {
+    Dense { words: Vec<u64>, len: usize },
+    Sparse(HashSet<PageId>),
+}
+
+impl Default for PageSet {
+    fn default() -> Self {
+        PageSet::Dense {
+            words: Vec::new(),
+            len: 0,
+        }
+    }
+}
+
+impl PageSet {
+    pub fn contains(&self, page: PageId) -> bool {
+        match self {
+            PageSet::Dense { words, .. } => words
+                .get(page as usize / 64)
+                .is_some_and(|word| word & (1 << (page % 64)) != 0),
+            PageSet::Sparse(pages) => pages.contains(&page),
+        }
+    }
+
+    /// Insert a page, returning whether it was not in the set yet.
+    pub fn insert(&mut self, page: PageId) -> bool {
+        match self {
+            PageSet::Dense { words, len } => {
+                let index = page as usize / 64;
+                if index >= words.len() {
+                    // Switch to a hash set if the bitset would be large and mostly empty.
+                    if index >= MAX_SPARSE_BITSET_WORDS && index >= *len {
+                        let pages = words
+                            .iter()
+                            .zip(0..)
+                            .flat_map(|(&word, index)| {
+                                (0..64)
+                                    .filter(move |bit| word & (1 << bit) != 0)
+                                    .map(move |bit| index * 64 + bit)
+                            })
+                            .collect();
+                        *self = PageSet::Sparse(pages);
+                        return self.insert(page);
+                    }
+                    words.resize(index + 1, 0);
+                }
+                let mask = 1 << (page % 64);
+                let is_new = words[index] & mask == 0;
+                words[index] |= mask;
+                *len += usize::from(is_new);
+                is_new
+            }
+            PageSet::Sparse(pages) => pages.insert(page),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            PageSet::Dense { len, .. } => *len,
+            PageSet::Sparse(pages) => pages.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_bytes_uses_binary_prefixes() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1024), "1.00 KiB");
+        assert_eq!(format_bytes(3 * 1024 * 1024 / 2), "1.50 MiB");
+    }
+
+    #[test]
+    fn parse_bytes_accepts_suffixes() {
+        assert_eq!(parse_bytes("100").unwrap(), 100);
+        assert_eq!(parse_bytes("4k").unwrap(), 4096);
+        assert_eq!(parse_bytes("2MiB").unwrap(), 2 * 1024 * 1024);
+        assert_eq!(parse_bytes("1 GB").unwrap(), 1024 * 1024 * 1024);
+    }
+
+    #[test]
+    fn parse_bytes_rejects_invalid_input() {
+        assert!(parse_bytes("").is_err());
+        assert!(parse_bytes("ten").is_err());
+        assert!(parse_bytes("10x").is_err());
+    }
+
+    #[test]
+    fn top_pages_keeps_highest_scores() {
+        let mut top = TopPages::new(2);
+        for (page, score) in [(1, 5), (2, 9), (3, 1), (4, 9)] {
+            top.add(page, score, ());
+        }
+        assert_eq!(top.into_pages(), vec![(2, 9, ()), (4, 9, ())]);
+    }
+
+    #[test]
+    fn page_set_grows_on_insert() {
+        let mut set = PageSet::default();
+        assert!(!set.contains(1000));
+        assert!(set.insert(1000));
+        assert!(!set.insert(1000));
+        assert!(set.insert(63));
+        assert!(set.contains(63) && set.contains(1000) && !set.contains(64));
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn page_set_falls_back_to_hash_set_for_sparse_ids() {
+        let mut set = PageSet::default();
+        assert!(set.insert(63));
+        assert!(set.insert(PageId::MAX));
+        assert!(matches!(set, PageSet::Sparse(_)));
+        assert!(!set.insert(63));
+        assert!(set.contains(63) && set.contains(PageId::MAX) && !set.contains(64));
+        assert_eq!(set.len(), 2);
+    }
+}
@@ -3,9 +3,15 @@ use crate::{
     dump::TableDumpFiles,
 };
 use anyhow::{anyhow, Result};
-use flate2::read::GzDecoder;
+use bzip2::read::MultiBzDecoder;
+use flate2::read::MultiGzDecoder;
 use regex::bytes::Regex;
-use std::{collections::HashMap, fs::File, io::Read, path::Path};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, Read},
+    path::Path,
+};
 
 const CHUNK_SIZE_BYTES: usize = 1024 * 1024; // 1MB
 const MAX_LINK_BATCH_SIZE: usize = 4_000_000;
@@ -127,7 +133,8 @@ fn pagelinks_row_pattern() -> String {
     .concat()
 }
 
-enum ParseSkipReason {
+/// Reasons for which a parsed row is not stored.
+pub enum ParseSkipReason {
     MissingTargetTitle,
     MissingTargetNamespace,
     MissingLinkTargetId,
@@ -135,11 +142,357 @@ enum ParseSkipReason {
     SelfLink,
 }
 
-enum ExtractMatch<T> {
+/// The outcome of extracting a single row: either a value to store, or a reason to skip it.
+pub enum ExtractMatch<T> {
     Store(T),
     Skip(ParseSkipReason),
 }
 
+/// Statistics about the rows extracted from a table, logged after parsing it.
+#[derive(Default)]
+pub struct ParseStats {
+    pub matches_total: usize,
+    pub overlap_deduped: usize,
+    extract_attempted: usize,
+    extract_malformed: usize,
+    skipped_missing_target_title: usize,
+    skipped_missing_target_namespace: usize,
+    skipped_missing_linktarget_id: usize,
+    skipped_self_redirect: usize,
+    skipped_self_link: usize,
+    stored: usize,
+}
+
+impl ParseStats {
+    /// Process the outcome of extracting a single row, storing it in the result if applicable.
+    /// Malformed rows are counted and skipped; only errors from storing are returned.
+    pub fn process<T, U>(
+        &mut self,
+        result: &mut U,
+        extracted: Result<ExtractMatch<T>>,
+        store_match: &impl Fn(&mut U, T) -> Result<()>,
+    ) -> Result<()> {
+        self.extract_attempted += 1;
+        match extracted {
+            Ok(ExtractMatch::Store(m)) => {
+                store_match(result, m)?;
+                self.stored += 1;
+            }
+            Ok(ExtractMatch::Skip(reason)) => match reason {
+                ParseSkipReason::MissingTargetTitle => {
+                    self.skipped_missing_target_title += 1;
+                }
+                ParseSkipReason::MissingTargetNamespace => {
+                    self.skipped_missing_target_namespace += 1;
+                }
+                ParseSkipReason::MissingLinkTargetId => {
+                    self.skipped_missing_linktarget_id += 1;
+                }
+                ParseSkipReason::SelfRedirect => {
+                    self.skipped_self_redirect += 1;
+                }
+                ParseSkipReason::SelfLink => {
+                    self.skipped_self_link += 1;
+                }
+            },
+            Err(e) => {
+                self.extract_malformed += 1;
+                log::trace!("regex match extraction malformed: {e}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Log the statistics, labeled with the source that was parsed.
+    pub fn log(&self, source: &str) {
+        let skipped_total = self.skipped_missing_target_title
+            + self.skipped_missing_target_namespace
+            + self.skipped_missing_linktarget_id
+            + self.skipped_self_redirect
+            + self.skipped_self_link;
+
+        #[allow(clippy::cast_precision_loss)]
+        let malformed_ratio = if self.extract_attempted == 0 {
+            0.0
+        } else {
+            self.extract_malformed as f64 / self.extract_attempted as f64
+        };
+
+        #[allow(clippy::cast_precision_loss)]
+        let skipped_ratio = if self.extract_attempted == 0 {
+            0.0
+        } else {
+            skipped_total as f64 / self.extract_attempted as f64
+        };
+
+        log::info!(
+            "parse stats [{}]: matches={}, deduped_overlap={}, attempted={}, malformed={}, skipped_total={}, skipped_missing_target_title={}, skipped_missing_target_namespace={}, skipped_missing_linktarget_id={}, skipped_self_redirect={}, skipped_self_link={}, stored={}, malformed_ratio={:.4}, skipped_ratio={:.4}",
+            source,
+            self.matches_total,
+            self.overlap_deduped,
+            self.extract_attempted,
+            self.extract_malformed,
+            skipped_total,
+            self.skipped_missing_target_title,
+            self.skipped_missing_target_namespace,
+            self.skipped_missing_linktarget_id,
+            self.skipped_self_redirect,
+            self.skipped_self_link,
+            self.stored,
+            malformed_ratio,
+            skipped_ratio,
+        );
+    }
+}
+
+/// A source of wiki table rows from which a database can be built, such as dump files.
+pub trait TableSource {
+    /// Parse the page table and return a mapping from page titles to page ids for each namespace.
+    fn parse_page_table(&self) -> Result<HashMap<PageNamespaceId, HashMap<String, PageId>>>;
+
+    /// Parse the redirect table and return a mapping from source page ids to target page ids.
+    fn parse_redirect_table(
+        &self,
+        title_to_id: &HashMap<PageNamespaceId, HashMap<String, PageId>>,
+    ) -> Result<HashMap<PageId, PageId>>;
+
+    /// Parse the linktarget table and return a mapping from link target ids to page ids.
+    fn parse_linktarget_table(
+        &self,
+        title_to_id: &HashMap<PageNamespaceId, HashMap<String, PageId>>,
+    ) -> Result<HashMap<LinkTargetId, PageId>>;
+
+    /// Parse the pagelinks table and output the parsed links in batches of incoming links.
+    fn parse_pagelinks_table<F: Fn(&mut IncomingLinkBatch) -> Result<()>>(
+        &self,
+        redirects: &HashMap<PageId, PageId>,
+        linktarget_to_target: &HashMap<LinkTargetId, PageId>,
+        output_link_batch: F,
+    ) -> Result<()>;
+}
+
+/// Look up the page id of a title in a namespace.
+fn resolve_title(
+    title_to_id: &HashMap<PageNamespaceId, HashMap<String, PageId>>,
+    namespace: PageNamespaceId,
+    title: &str,
+) -> ExtractMatch<PageId> {
+    if let Some(namespace_map) = title_to_id.get(&namespace) {
+        if let Some(id) = namespace_map.get(title) {
+            ExtractMatch::Store(*id)
+        } else {
+            ExtractMatch::Skip(ParseSkipReason::MissingTargetTitle)
+        }
+    } else {
+        ExtractMatch::Skip(ParseSkipReason::MissingTargetNamespace)
+    }
+}
+
+/// Resolve a row of the redirect table to a pair of source and target page ids.
+pub fn resolve_redirect(
+    title_to_id: &HashMap<PageNamespaceId, HashMap<String, PageId>>,
+    source: PageId,
+    target_namespace: PageNamespaceId,
+    target_title: &str,
+) -> ExtractMatch<(PageId, PageId)> {
+    let target = match resolve_title(title_to_id, target_namespace, target_title) {
+        ExtractMatch::Store(target) => target,
+        ExtractMatch::Skip(reason) => return ExtractMatch::Skip(reason),
+    };
+
+    if source == target {
+        return ExtractMatch::Skip(ParseSkipReason::SelfRedirect);
+    }
+
+    ExtractMatch::Store((source, target))
+}
+
+/// Resolve a row of the linktarget table to a pair of link target id and page id.
+pub fn resolve_linktarget(
+    title_to_id: &HashMap<PageNamespaceId, HashMap<String, PageId>>,
+    linktarget: LinkTargetId,
+    target_namespace: PageNamespaceId,
+    target_title: &str,
+) -> ExtractMatch<(LinkTargetId, PageId)> {
+    match resolve_title(title_to_id, target_namespace, target_title) {
+        ExtractMatch::Store(target) => ExtractMatch::Store((linktarget, target)),
+        ExtractMatch::Skip(reason) => ExtractMatch::Skip(reason),
+    }
+}
+
+/// Resolve a row of the pagelinks table to a pair of source and target page ids, following
+/// redirects on both ends.
+pub fn resolve_link(
+    redirects: &HashMap<PageId, PageId>,
+    linktarget_to_target: &HashMap<LinkTargetId, PageId>,
+    source: PageId,
+    linktarget: LinkTargetId,
+) -> ExtractMatch<(PageId, PageId)> {
+    let target = if let Some(target) = linktarget_to_target.get(&linktarget) {
+        *target
+    } else {
+        return ExtractMatch::Skip(ParseSkipReason::MissingLinkTargetId);
+    };
+
+    let source = *redirects.get(&source).unwrap_or(&source);
+    let target = *redirects.get(&target).unwrap_or(&target);
+
+    if source == target {
+        return ExtractMatch::Skip(ParseSkipReason::SelfLink);
+    }
+
+    ExtractMatch::Store((source, target))
+}
+
+/// Store a parsed page title, returning an error if the title already has another page id.
+pub fn store_page_title(
+    result: &mut HashMap<PageNamespaceId, HashMap<String, PageId>>,
+    (id, namespace, title): (PageId, PageNamespaceId, String),
+) -> Result<()> {
+    let namespace_map = result.entry(namespace).or_default();
+    if let Some(prev) = namespace_map.insert(title, id) {
+        if prev != id {
+            return Err(anyhow!(
+                "two page ids for same title found in namespace {namespace}: {prev} & {id}"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Store a parsed redirect, returning an error if the source already has another target.
+pub fn store_redirect(
+    result: &mut HashMap<PageId, PageId>,
+    (source, target): (PageId, PageId),
+) -> Result<()> {
+    if let Some(prev) = result.insert(source, target) {
+        if prev != target {
+            return Err(anyhow!(
+                "two redirect targets for same source found: {prev} & {target}"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Store a parsed link target, returning an error if it already points to another page id.
+pub fn store_linktarget(
+    result: &mut HashMap<LinkTargetId, PageId>,
+    (linktarget, target): (LinkTargetId, PageId),
+) -> Result<()> {
+    if let Some(prev) = result.insert(linktarget, target) {
+        if prev != target {
+            return Err(anyhow!(
+                "two page ids with same linktarget found: {prev} & {target}"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Store a parsed link in a batch, outputting the batch when it reaches its maximum size.
+pub fn store_link(
+    batch: &mut IncomingLinkBatch,
+    (source, target): (PageId, PageId),
+    output_link_batch: &impl Fn(&mut IncomingLinkBatch) -> Result<()>,
+) -> Result<()> {
+    batch.insert(source, target);
+    if batch.size() > MAX_LINK_BATCH_SIZE {
+        output_link_batch(batch)?;
+        if batch.size() > 0 {
+            return Err(anyhow!("link batch not properly drained"));
+        }
+    }
+    Ok(())
+}
+
+/// Output the last, partially filled batch of links.
+pub fn finish_links(
+    mut remaining_batch: IncomingLinkBatch,
+    output_link_batch: &impl Fn(&mut IncomingLinkBatch) -> Result<()>,
+) -> Result<()> {
+    output_link_batch(&mut remaining_batch)?;
+    if remaining_batch.size() > 0 {
+        return Err(anyhow!("link batch not properly drained"));
+    }
+    Ok(())
+}
+
+/// The compression formats in which dump files can be read.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Compression {
+    None,
+    Gzip,
+    Bzip2,
+    Zstd,
+}
+
+impl Compression {
+    /// Detect the compression of a file from its magic bytes, falling back to its extension when
+    /// the magic bytes are not recognized (e.g. for empty files). Files without a recognized magic
+    /// number or extension are assumed to be uncompressed.
+    fn detect(path: &Path) -> Result<Self> {
+        let mut magic = [0; 4];
+        let mut file = File::open(path)?;
+        let mut read = 0;
+        while read < magic.len() {
+            let count = file.read(&mut magic[read..])?;
+            if count == 0 {
+                break;
+            }
+            read += count;
+        }
+
+        if let Some(compression) = Self::from_magic(&magic[..read]) {
+            return Ok(compression);
+        }
+
+        let extension = path.extension().and_then(|s| s.to_str()).unwrap_or("");
+        Ok(Self::from_extension(extension).unwrap_or(Self::None))
+    }
+
+    fn from_magic(magic: &[u8]) -> Option<Self> {
+        if magic.starts_with(&[0x1f, 0x8b]) {
+            Some(Self::Gzip)
+        } else if magic.starts_with(b"BZh") {
+            Some(Self::Bzip2)
+        } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Self::Zstd)
+        } else {
+            None
+        }
+    }
+
+    fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "gz" => Some(Self::Gzip),
+            "bz2" => Some(Self::Bzip2),
+            "zst" => Some(Self::Zstd),
+            "sql" => Some(Self::None),
+            _ => None,
+        }
+    }
+
+    /// Open a file for reading its decompressed contents.
+    fn open(self, path: &Path) -> Result<Box<dyn Read>> {
+        let file = File::open(path)?;
+        Ok(match self {
+            Self::None => Box::new(BufReader::new(file)),
+            Self::Gzip => Box::new(MultiGzDecoder::new(file)),
+            Self::Bzip2 => Box::new(MultiBzDecoder::new(file)),
+            Self::Zstd => Box::new(zstd::Decoder::new(file)?),
+        })
+    }
+}
+
+/// Open a file for reading its contents, decompressing it if needed. The compression format is
+/// detected automatically, see `Compression::detect`.
+pub fn open_decompressed(path: &Path) -> Result<Box<dyn Read>> {
+    let compression = Compression::detect(path)?;
+    log::debug!("reading '{}' as {compression:?}", path.display());
+    compression.open(path)
+}
+
 /// Struct representing a batch of links stored in the incoming format.
 #[derive(Debug, Default)]
 pub struct IncomingLinkBatch {
@@ -168,10 +521,8 @@ impl IncomingLinkBatch {
     }
 }
 
-impl TableDumpFiles {
-    /// Parse the page table dump file and return a mapping from page titles to page ids for each
-    /// namespace.
-    pub fn parse_page_table(&self) -> Result<HashMap<PageNamespaceId, HashMap<String, PageId>>> {
+impl TableSource for TableDumpFiles {
+    fn parse_page_table(&self) -> Result<HashMap<PageNamespaceId, HashMap<String, PageId>>> {
         sliding_regex_file(
             self.page.as_path(),
             &Regex::new(&page_row_pattern())?,
@@ -196,23 +547,11 @@ impl TableDumpFiles {
 
                 Ok(ExtractMatch::Store((id, namespace, title)))
             },
-            |result: &mut HashMap<PageNamespaceId, HashMap<String, PageId>>,
-             (id, namespace, title)| {
-                let namespace_map = result.entry(namespace).or_insert_with(HashMap::new);
-                if let Some(prev) = namespace_map.insert(title, id) {
-                    if prev != id {
-                        return Err(anyhow!(
-                            "two page ids for same title found in namespace {namespace}: {prev} & {id}"
-                        ));
-                    }
-                }
-                Ok(())
-            },
+            store_page_title,
         )
     }
 
-    /// Parse the redirect table dump file and return a mapping from source page ids to target page ids.
-    pub fn parse_redirect_table(
+    fn parse_redirect_table(
         &self,
         title_to_id: &HashMap<PageNamespaceId, HashMap<String, PageId>>,
     ) -> Result<HashMap<PageId, PageId>> {
@@ -233,41 +572,23 @@ impl TableDumpFiles {
                     str.parse::<PageNamespaceId>()?
                 };
 
-                let target = {
+                let target_title = {
                     let m = caps.get(3).unwrap(); // Capture 3 always participates in the match
-                    let str = std::str::from_utf8(m.as_bytes())?;
-                    if let Some(namespace_map) = title_to_id.get(&target_namespace) {
-                        if let Some(id) = namespace_map.get(str) {
-                            *id
-                        } else {
-                            return Ok(ExtractMatch::Skip(ParseSkipReason::MissingTargetTitle));
-                        }
-                    } else {
-                        return Ok(ExtractMatch::Skip(ParseSkipReason::MissingTargetNamespace));
-                    }
+                    std::str::from_utf8(m.as_bytes())?
                 };
 
-                if source == target {
-                    return Ok(ExtractMatch::Skip(ParseSkipReason::SelfRedirect));
-                }
-
-                Ok(ExtractMatch::Store((source, target)))
-            },
-            |result: &mut HashMap<PageId, PageId>, (source, target)| {
-                if let Some(prev) = result.insert(source, target) {
-                    if prev != target {
-                        return Err(anyhow!(
-                            "two redirect targets for same source found: {prev} & {target}"
-                        ));
-                    }
-                }
-                Ok(())
+                Ok(resolve_redirect(
+                    title_to_id,
+                    source,
+                    target_namespace,
+                    target_title,
+                ))
             },
+            store_redirect,
         )
     }
 
-    /// Parse the linktarget table dump file and return a mapping from link target ids to page ids.
-    pub fn parse_linktarget_table(
+    fn parse_linktarget_table(
         &self,
         title_to_id: &HashMap<PageNamespaceId, HashMap<String, PageId>>,
     ) -> Result<HashMap<LinkTargetId, PageId>> {
@@ -288,32 +609,19 @@ impl TableDumpFiles {
                     str.parse::<PageNamespaceId>()?
                 };
 
-                let target = {
+                let target_title = {
                     let m = caps.get(3).unwrap(); // Capture 3 always participates in the match
-                    let str = std::str::from_utf8(m.as_bytes())?;
-                    if let Some(namespace_map) = title_to_id.get(&target_namespace) {
-                        if let Some(id) = namespace_map.get(str) {
-                            *id
-                        } else {
-                            return Ok(ExtractMatch::Skip(ParseSkipReason::MissingTargetTitle));
-                        }
-                    } else {
-                        return Ok(ExtractMatch::Skip(ParseSkipReason::MissingTargetNamespace));
-                    }
+                    std::str::from_utf8(m.as_bytes())?
                 };
 
-                Ok(ExtractMatch::Store((linktarget, target)))
-            },
-            |result: &mut HashMap<LinkTargetId, PageId>, (linktarget, target)| {
-                if let Some(prev) = result.insert(linktarget, target) {
-                    if prev != target {
-                        return Err(anyhow!(
-                            "two page ids with same linktarget found: {prev} & {target}"
-                        ));
-                    }
-                }
-                Ok(())
+                Ok(resolve_linktarget(
+                    title_to_id,
+                    linktarget,
+                    target_namespace,
+                    target_title,
+                ))
             },
+            store_linktarget,
         )
     }
 
@@ -328,13 +636,13 @@ impl TableDumpFiles {
     ///
     /// The page ids in the lists are not strictly unique, as the parsing process may output the same
     /// link multiple times occasionally.
-    pub fn parse_pagelinks_table<F: Fn(&mut IncomingLinkBatch) -> Result<()>>(
+    fn parse_pagelinks_table<F: Fn(&mut IncomingLinkBatch) -> Result<()>>(
         &self,
         redirects: &HashMap<PageId, PageId>,
         linktarget_to_target: &HashMap<LinkTargetId, PageId>,
         output_link_batch: F,
     ) -> Result<()> {
-        let mut remaining_batch = sliding_regex_file(
+        let remaining_batch = sliding_regex_file(
             self.pagelinks.as_path(),
             &Regex::new(&pagelinks_row_pattern())?,
             PAGELINKS_MAX_MATCH_SIZE_BYTES,
@@ -351,43 +659,22 @@ impl TableDumpFiles {
                     str.parse::<LinkTargetId>()?
                 };
 
-                let target = if let Some(target) = linktarget_to_target.get(&linktarget) {
-                    *target
-                } else {
-                    return Ok(ExtractMatch::Skip(ParseSkipReason::MissingLinkTargetId));
-                };
-
-                let source = *redirects.get(&source).unwrap_or(&source);
-                let target = *redirects.get(&target).unwrap_or(&target);
-
-                if source == target {
-                    return Ok(ExtractMatch::Skip(ParseSkipReason::SelfLink));
-                }
-
-                Ok(ExtractMatch::Store((source, target)))
-            },
-            |batch: &mut IncomingLinkBatch, (source, target)| {
-                batch.insert(source, target);
-                if batch.size() > MAX_LINK_BATCH_SIZE {
-                    output_link_batch(batch)?;
-                    if batch.size() > 0 {
-                        return Err(anyhow!("link batch not properly drained"));
-                    }
-                }
-                Ok(())
+                Ok(resolve_link(
+                    redirects,
+                    linktarget_to_target,
+                    source,
+                    linktarget,
+                ))
             },
+            |batch: &mut IncomingLinkBatch, link| store_link(batch, link, &output_link_batch),
         )?;
 
-        output_link_batch(&mut remaining_batch)?;
-        if remaining_batch.size() > 0 {
-            return Err(anyhow!("link batch not properly drained"));
-        }
-
-        Ok(())
+        finish_links(remaining_batch, &output_link_batch)
     }
 }
 
-/// Parse a file by running a regex on its contents in a sliding window fashion. Regex captures
+/// Parse a file by running a regex on its (decompressed) contents in a sliding window fashion. The
+/// compression format is detected automatically, see `Compression::detect`. Regex captures
 /// are extracted using a function and stored using another function. The sliding window size is
 /// specified in bytes (max match size), to ensure that the regex can match across chunk boundaries
 /// when reading the file.
@@ -408,20 +695,6 @@ fn sliding_regex_file<
         end: usize,
     }
 
-    #[derive(Default)]
-    struct ParseStats {
-        regex_matches_total: usize,
-        overlap_deduped: usize,
-        extract_attempted: usize,
-        extract_malformed: usize,
-        skipped_missing_target_title: usize,
-        skipped_missing_target_namespace: usize,
-        skipped_missing_linktarget_id: usize,
-        skipped_self_redirect: usize,
-        skipped_self_link: usize,
-        stored: usize,
-    }
-
     impl Default for Chunk {
         fn default() -> Self {
             Self {
@@ -431,8 +704,7 @@ fn sliding_regex_file<
         }
     }
 
-    let file = File::open(path)?;
-    let mut reader = GzDecoder::new(file);
+    let mut reader = open_decompressed(path)?;
     let mut result = U::default();
     let mut stats = ParseStats::default();
 
@@ -455,7 +727,7 @@ fn sliding_regex_file<
 
         // Process the current chunk by running the regex on it.
         for captures in regex.captures_iter(&cur_chunk.data[..cur_chunk.end]) {
-            stats.regex_matches_total += 1;
+            stats.matches_total += 1;
 
             let full_match = captures.get(0).unwrap(); // Full match always participates
             if full_match.end() <= overlap {
@@ -463,75 +735,14 @@ fn sliding_regex_file<
                 continue;
             }
 
-            stats.extract_attempted += 1;
-            match extract_match(&captures) {
-                Ok(ExtractMatch::Store(m)) => {
-                    store_match(&mut result, m)?;
-                    stats.stored += 1;
-                }
-                Ok(ExtractMatch::Skip(reason)) => match reason {
-                    ParseSkipReason::MissingTargetTitle => {
-                        stats.skipped_missing_target_title += 1;
-                    }
-                    ParseSkipReason::MissingTargetNamespace => {
-                        stats.skipped_missing_target_namespace += 1;
-                    }
-                    ParseSkipReason::MissingLinkTargetId => {
-                        stats.skipped_missing_linktarget_id += 1;
-                    }
-                    ParseSkipReason::SelfRedirect => {
-                        stats.skipped_self_redirect += 1;
-                    }
-                    ParseSkipReason::SelfLink => {
-                        stats.skipped_self_link += 1;
-                    }
-                },
-                Err(e) => {
-                    stats.extract_malformed += 1;
-                    log::trace!("regex match extraction malformed: {e}");
-                }
-            }
+            stats.process(&mut result, extract_match(&captures), &store_match)?;
         }
 
         // Make the current chunk the previous chunk.
         std::mem::swap(&mut prev_chunk.data, &mut cur_chunk.data);
     }
 
-    let skipped_total = stats.skipped_missing_target_title
-        + stats.skipped_missing_target_namespace
-        + stats.skipped_missing_linktarget_id
-        + stats.skipped_self_redirect
-        + stats.skipped_self_link;
-
-    let malformed_ratio = if stats.extract_attempted == 0 {
-        0.0
-    } else {
-        stats.extract_malformed as f64 / stats.extract_attempted as f64
-    };
-
-    let skipped_ratio = if stats.extract_attempted == 0 {
-        0.0
-    } else {
-        skipped_total as f64 / stats.extract_attempted as f64
-    };
-
-    log::info!(
-        "parse stats [{}]: matches={}, deduped_overlap={}, attempted={}, malformed={}, skipped_total={}, skipped_missing_target_title={}, skipped_missing_target_namespace={}, skipped_missing_linktarget_id={}, skipped_self_redirect={}, skipped_self_link={}, stored={}, malformed_ratio={:.4}, skipped_ratio={:.4}",
-        path.display(),
-        stats.regex_matches_total,
-        stats.overlap_deduped,
-        stats.extract_attempted,
-        stats.extract_malformed,
-        skipped_total,
-        stats.skipped_missing_target_title,
-        stats.skipped_missing_target_namespace,
-        stats.skipped_missing_linktarget_id,
-        stats.skipped_self_redirect,
-        stats.skipped_self_link,
-        stats.stored,
-        malformed_ratio,
-        skipped_ratio,
-    );
+    stats.log(&path.display().to_string());
 
     Ok(result)
 }
@@ -739,6 +950,81 @@ mod tests {
         assert!(!regex.is_match(row));
     }
 
+    #[test]
+    fn compression_detected_from_magic_bytes() {
+        assert_eq!(
+            Compression::from_magic(&[0x1f, 0x8b, 0x08, 0x00]),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(Compression::from_magic(b"BZh9"), Some(Compression::Bzip2));
+        assert_eq!(
+            Compression::from_magic(&[0x28, 0xb5, 0x2f, 0xfd]),
+            Some(Compression::Zstd)
+        );
+        assert_eq!(Compression::from_magic(b"-- M"), None);
+        assert_eq!(Compression::from_magic(&[]), None);
+    }
+
+    #[test]
+    fn compression_detected_from_extension() {
+        assert_eq!(Compression::from_extension("gz"), Some(Compression::Gzip));
+        assert_eq!(Compression::from_extension("bz2"), Some(Compression::Bzip2));
+        assert_eq!(Compression::from_extension("zst"), Some(Compression::Zstd));
+        assert_eq!(Compression::from_extension("sql"), Some(Compression::None));
+        assert_eq!(Compression::from_extension("txt"), None);
+    }
+
+    #[test]
+    fn sliding_regex_file_reads_all_compressions() {
+        use std::io::Write;
+
+        let sql = b"INSERT INTO `pagelinks` VALUES (1,0,2),(3,0,4),(5,0,6);\n";
+        let dir = std::env::temp_dir().join(format!("wikipath-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let gzip = {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(sql).unwrap();
+            encoder.finish().unwrap()
+        };
+        let bzip2 = {
+            let mut encoder =
+                bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+            encoder.write_all(sql).unwrap();
+            encoder.finish().unwrap()
+        };
+        let zstd = zstd::encode_all(&sql[..], 0).unwrap();
+
+        let regex = Regex::new(&pagelinks_row_pattern()).unwrap();
+        for (name, data) in [
+            ("links.sql", sql.to_vec()),
+            ("links.sql.gz", gzip),
+            ("links.sql.bz2", bzip2),
+            ("links.sql.zst", zstd),
+        ] {
+            let path = dir.join(name);
+            std::fs::write(&path, data).unwrap();
+            let links = sliding_regex_file(
+                &path,
+                &regex,
+                PAGELINKS_MAX_MATCH_SIZE_BYTES,
+                |caps| {
+                    let source = std::str::from_utf8(caps.get(1).unwrap().as_bytes())?;
+                    Ok(ExtractMatch::Store(source.parse::<PageId>()?))
+                },
+                |result: &mut Vec<PageId>, source| {
+                    result.push(source);
+                    Ok(())
+                },
+            )
+            .unwrap();
+            assert_eq!(links, vec![1, 3, 5], "{name}");
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
     #[test]
     fn cleanup_redirects_flattens_chains() {
         let mut redirs = HashMap::new();
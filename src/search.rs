@@ -1,7 +1,265 @@
-use crate::database::{Database, PageId};
-use anyhow::Result;
-use serde::Serialize;
-use std::collections::{HashMap, HashSet, VecDeque};
+use crate::{
+    database::{Database, PageId},
+    misc::PageSet,
+};
+use anyhow::{ensure, Result};
+use heed::RoTxn;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt,
+    ops::RangeInclusive,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+
+/// Frontiers with at least this many pages are expanded in parallel.
+const PARALLEL_THRESHOLD: usize = 1000;
+
+/// The number of frontier pages expanded by a single parallel task, using its own read transaction.
+const PARALLEL_CHUNK_SIZE: usize = 250;
+
+/// The largest path count that is reported exactly, which is the largest integer that can be
+/// represented exactly by a JavaScript number (`Number.MAX_SAFE_INTEGER`).
+const MAX_EXACT_COUNT: u64 = (1 << 53) - 1;
+
+/// The maximum number of paths enumerated when searching for paths longer than the shortest.
+const MAX_NEAR_PATHS: usize = 10_000;
+
+/// The maximum number of pages ranked for each node that still fits in a subgraph, when a level of
+/// the subgraph does not fit entirely.
+const SUBGRAPH_RANK_FACTOR: usize = 4;
+
+/// The reasons a search can be stopped before it completes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchError {
+    /// The search was cancelled, e.g. because the client went away.
+    Cancelled,
+    /// The deadline of the search passed.
+    TimedOut,
+}
+
+impl fmt::Display for SearchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SearchError::Cancelled => write!(f, "search was cancelled"),
+            SearchError::TimedOut => write!(f, "search timed out"),
+        }
+    }
+}
+
+impl std::error::Error for SearchError {}
+
+/// Limits on the resources used by a single search.
+#[derive(Debug, Clone, Default)]
+pub struct SearchLimits {
+    pub cancellation: Cancellation,
+    pub max_visited: Option<usize>, // pages visited in both directions combined
+    pub max_fetched: Option<usize>, // adjacency list entries fetched from the database
+}
+
+impl SearchLimits {
+    fn budget_exceeded(&self, visited: usize, fetched: usize) -> bool {
+        self.max_visited.is_some_and(|max| visited > max)
+            || self.max_fetched.is_some_and(|max| fetched > max)
+    }
+}
+
+/// Constraints on the links that paths follow and the pages that they go through. Redirects are
+/// followed for all given pages.
+#[derive(Debug, Clone, Default)]
+pub struct PathConstraints {
+    pub avoid: Vec<PageId>,            // pages that paths may not go through
+    pub max_in_degree: Option<usize>,  // skip pages with more incoming links, e.g. hubs
+    pub max_out_degree: Option<usize>, // skip pages with more outgoing links
+    pub via: Vec<PageId>,              // pages that paths must go through, in this order
+    pub undirected: bool,              // links may also be followed against their direction
+}
+
+/// The direction in which links are followed from a page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum LinkDirection {
+    #[serde(rename = "out")]
+    Outgoing,
+    #[serde(rename = "in")]
+    Incoming,
+    #[serde(rename = "both")]
+    Both,
+}
+
+impl LinkDirection {
+    fn reversed(self) -> Self {
+        match self {
+            LinkDirection::Outgoing => LinkDirection::Incoming,
+            LinkDirection::Incoming => LinkDirection::Outgoing,
+            LinkDirection::Both => LinkDirection::Both,
+        }
+    }
+}
+
+/// The pages that paths may not go through, with redirects of the avoided pages followed.
+#[derive(Debug, Default)]
+struct Avoid {
+    pages: HashSet<PageId>,
+    max_in_degree: Option<usize>,
+    max_out_degree: Option<usize>,
+    degree_checks: Mutex<HashMap<PageId, DegreeCheck>>, // cached for the whole search
+}
+
+/// Whether a page passes the degree thresholds, along with the number of adjacency list entries
+/// fetched to decide it. These are only counted towards the budget the first time they are needed.
+#[derive(Debug, Clone, Copy)]
+struct DegreeCheck {
+    allowed: bool,
+    fetched: usize,
+    charged: bool,
+}
+
+impl Avoid {
+    fn checks_degree(&self) -> bool {
+        self.max_in_degree.is_some() || self.max_out_degree.is_some()
+    }
+
+    /// Check whether a page passes the degree thresholds, fetching its links only if the page was
+    /// not checked before during this search.
+    fn allows_degree(&self, db: &Database, txn: &RoTxn<'_>, page: PageId) -> Result<bool> {
+        if let Some(check) = self.degree_checks.lock().unwrap().get(&page) {
+            return Ok(check.allowed);
+        }
+
+        let mut allowed = true;
+        let mut fetched = 0;
+        if let Some(max) = self.max_in_degree {
+            let degree = db.get_incoming_links(txn, page)?.len();
+            fetched += degree;
+            allowed = degree <= max;
+        }
+        if let Some(max) = self.max_out_degree.filter(|_| allowed) {
+            let degree = db.get_outgoing_links(txn, page)?.len();
+            fetched += degree;
+            allowed = degree <= max;
+        }
+        self.degree_checks
+            .lock()
+            .unwrap()
+            .entry(page)
+            .or_insert(DegreeCheck {
+                allowed,
+                fetched,
+                charged: false,
+            });
+        Ok(allowed)
+    }
+
+    /// Get the number of adjacency list entries fetched to check the degree of the given pages that
+    /// were not counted towards the budget yet, and mark them as counted.
+    fn charge_degree_checks(&self, pages: &[PageId]) -> usize {
+        let mut degree_checks = self.degree_checks.lock().unwrap();
+        let mut fetched = 0;
+        for page in pages {
+            if let Some(check) = degree_checks.get_mut(page).filter(|check| !check.charged) {
+                check.charged = true;
+                fetched += check.fetched;
+            }
+        }
+        fetched
+    }
+}
+
+/// Decides which linked pages are added to the next level of a BFS: pages that were not visited
+/// yet and are not avoided. The root of the other direction is never avoided.
+struct LevelFilter<'a> {
+    visited: &'a PageSet,
+    avoid: &'a Avoid,
+    other_root: Option<PageId>, // only for a bidirectional BFS
+}
+
+impl LevelFilter<'_> {
+    /// Whether the page is allowed. Pages whose degree had to be checked are added to
+    /// `degree_checked`, so the fetched links can be counted towards the budget.
+    fn allows(
+        &self,
+        db: &Database,
+        txn: &RoTxn<'_>,
+        page: PageId,
+        degree_checked: &mut Vec<PageId>,
+    ) -> Result<bool> {
+        if self.visited.contains(page) {
+            return Ok(false);
+        }
+        if Some(page) == self.other_root {
+            return Ok(true);
+        }
+        if self.avoid.pages.contains(&page) {
+            return Ok(false);
+        }
+        if self.avoid.checks_degree() {
+            degree_checked.push(page);
+            return self.avoid.allows_degree(db, txn, page);
+        }
+        Ok(true)
+    }
+}
+
+/// The links fetched from a page by a BFS.
+struct FetchedLinks {
+    count: usize,                // number of links fetched
+    allowed: Vec<PageId>,        // linked pages allowed by the level filter
+    degree_checked: Vec<PageId>, // linked pages whose degree was checked by the level filter
+}
+
+/// How far a search got before it was stopped because it exceeded its budget.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchProgress {
+    forward_depth: u32,
+    backward_depth: u32,
+    forward_frontier: usize,
+    backward_frontier: usize,
+    forward_visited: usize,
+    backward_visited: usize,
+    fetched: usize,
+}
+
+/// A handle to stop a running search, either explicitly or once a deadline has passed. Clones
+/// share the same cancellation state. The search checks it between BFS levels and pages.
+#[derive(Debug, Clone, Default)]
+pub struct Cancellation {
+    cancelled: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+}
+
+impl Cancellation {
+    pub fn with_deadline(deadline: Instant) -> Self {
+        Self {
+            cancelled: Arc::default(),
+            deadline: Some(deadline),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Return an error if the search should stop.
+    fn check(&self) -> Result<(), SearchError> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            Err(SearchError::Cancelled)
+        } else if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            Err(SearchError::TimedOut)
+        } else {
+            Ok(())
+        }
+    }
+}
 
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -11,16 +269,279 @@ pub struct Paths<'a> {
     target: PageId,
     target_is_redirect: bool,
     links: HashMap<PageId, HashSet<PageId>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    segments: Vec<PathSegment>, // only for paths via other pages, of which `links` is the union
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    titles: HashMap<PageId, String>, // only for databases containing titles, e.g. imported ones
     language_code: &'a str,
     date_code: &'a str,
     length: u32,
-    count: u32,
+    count: u64,
+    count_is_approximate: bool, // if so, there are more paths than the count
+    #[serde(skip_serializing_if = "Option::is_none")]
+    incomplete: Option<SearchProgress>, // only if the search exceeded its budget
+}
+
+/// The shortest paths between two consecutive pages that paths must go through.
+#[derive(Debug, Serialize)]
+pub struct PathSegment {
+    source: PageId,
+    target: PageId,
+    links: HashMap<PageId, HashSet<PageId>>,
+    length: u32,
+    count: u64,
+}
+
+/// The state of a BFS, or of one direction of a bidirectional BFS: the visited pages, and the pages
+/// at each depth, of which the last level is the frontier.
+#[derive(Debug)]
+struct Bfs {
+    visited: PageSet,
+    levels: Vec<Vec<PageId>>,
+    direction: LinkDirection, // in which links are followed to the next level
+}
+
+impl Bfs {
+    fn new(root: PageId, direction: LinkDirection) -> Self {
+        let mut visited = PageSet::default();
+        visited.insert(root);
+        Self {
+            visited,
+            levels: vec![vec![root]],
+            direction,
+        }
+    }
+
+    fn frontier(&self) -> &[PageId] {
+        self.levels.last().unwrap() // there is always at least the root level
+    }
+
+    /// Get the depth at which a page was visited. The levels must be sorted.
+    fn depth(&self, page: PageId) -> Option<u32> {
+        if !self.visited.contains(page) {
+            return None;
+        }
+        (0..)
+            .zip(&self.levels)
+            .find_map(|(depth, level)| level.binary_search(&page).is_ok().then_some(depth))
+    }
+
+    /// Reconstruct the predecessors of the pages on the shortest paths from the root to the given
+    /// pages in the frontier. Walking back one level at a time, the predecessors of a page are the
+    /// pages of the previous level it is linked to, which are fetched with `get_links`.
+    fn predecessors(
+        mut self,
+        pages: &HashSet<PageId>,
+        mut get_links: impl FnMut(PageId) -> Result<Vec<PageId>>,
+    ) -> Result<HashMap<PageId, HashSet<PageId>>> {
+        let mut predecessors = HashMap::new();
+        let mut current: Vec<PageId> = pages.iter().copied().collect();
+        for level in self.levels.iter_mut().rev().skip(1) {
+            level.sort_unstable();
+            let mut previous = HashSet::new();
+            for page in current {
+                let page_predecessors: HashSet<PageId> = get_links(page)?
+                    .into_iter()
+                    .filter(|linked| level.binary_search(linked).is_ok())
+                    .collect();
+                previous.extend(&page_predecessors);
+                predecessors.insert(page, page_predecessors);
+            }
+            current = previous.into_iter().collect();
+        }
+        Ok(predecessors)
+    }
+}
+
+/// The state of a BFS in both directions between a source and a target page.
+#[derive(Debug)]
+struct BidirectionalBfs {
+    forward: Bfs,
+    backward: Bfs,
+    forward_depth: u32,
+    backward_depth: u32,
+    overlapping: HashSet<PageId>, // pages visited in both directions
+    fetched: usize,               // adjacency list entries fetched
+}
+
+impl BidirectionalBfs {
+    fn new(source: PageId, target: PageId, undirected: bool) -> Self {
+        let (forward_direction, backward_direction) = if undirected {
+            (LinkDirection::Both, LinkDirection::Both)
+        } else {
+            (LinkDirection::Outgoing, LinkDirection::Incoming)
+        };
+        Self {
+            forward: Bfs::new(source, forward_direction),
+            backward: Bfs::new(target, backward_direction),
+            forward_depth: 0,
+            backward_depth: 0,
+            overlapping: if source == target {
+                HashSet::from([source])
+            } else {
+                HashSet::new()
+            },
+            fetched: 0,
+        }
+    }
+
+    /// Whether either direction has no pages left to expand.
+    fn is_exhausted(&self) -> bool {
+        self.forward.frontier().is_empty() || self.backward.frontier().is_empty()
+    }
+
+    fn progress(&self) -> SearchProgress {
+        SearchProgress {
+            forward_depth: self.forward_depth,
+            backward_depth: self.backward_depth,
+            forward_frontier: self.forward.frontier().len(),
+            backward_frontier: self.backward.frontier().len(),
+            forward_visited: self.forward.visited.len(),
+            backward_visited: self.backward.visited.len(),
+            fetched: self.fetched,
+        }
+    }
 }
 
 impl Database {
-    /// Get the shortest paths between two pages.
-    #[allow(clippy::too_many_lines)]
-    pub fn get_shortest_paths(&self, source: PageId, target: PageId) -> Result<Paths<'_>> {
+    /// Expand the frontier of a BFS by one level, leaving out avoided pages. For a bidirectional
+    /// BFS, the pages visited by the other direction count towards the budget. Returns false if
+    /// the search exceeded its budget, in which case the level is not completed.
+    fn expand_bfs(
+        &self,
+        txn: &RoTxn<'_>,
+        bfs: &mut Bfs,
+        other: Option<&Bfs>,
+        avoid: &Avoid,
+        fetched: &mut usize,
+        limits: &SearchLimits,
+    ) -> Result<bool> {
+        let cancellation = &limits.cancellation;
+        cancellation.check()?;
+        let other_visited = other.map_or(0, |other| other.visited.len());
+
+        // Expand each page of the frontier into the next level. Pages are marked as visited as
+        // soon as they are found, since their predecessors are reconstructed afterwards. Large
+        // frontiers are fetched in parallel batches, but the fetched links are always merged in
+        // frontier order, so the result does not depend on the number of threads.
+        let frontier = bfs.levels.last().unwrap();
+        let is_parallel = frontier.len() >= PARALLEL_THRESHOLD;
+        let batch_size = if is_parallel {
+            PARALLEL_CHUNK_SIZE * rayon::current_num_threads()
+        } else {
+            frontier.len()
+        };
+        let mut next = Vec::new();
+        let mut unexpanded = None;
+        'level: for (batch_index, batch) in frontier.chunks(batch_size).enumerate() {
+            let filter = LevelFilter {
+                visited: &bfs.visited,
+                avoid,
+                other_root: other.map(|other| other.levels[0][0]),
+            };
+            let batch_links = if is_parallel {
+                batch
+                    .par_chunks(PARALLEL_CHUNK_SIZE)
+                    .map(|chunk| -> Result<_> {
+                        // Each task uses its own read transaction.
+                        let txn = self.read_txn()?;
+                        let links = self.fetch_unvisited(
+                            &txn,
+                            chunk,
+                            bfs.direction,
+                            &filter,
+                            cancellation,
+                        )?;
+                        txn.commit()?;
+                        Ok(links)
+                    })
+                    .collect::<Result<Vec<_>>>()?
+                    .into_iter()
+                    .flatten()
+                    .collect()
+            } else {
+                self.fetch_unvisited(txn, batch, bfs.direction, &filter, cancellation)?
+            };
+
+            for (i, links) in batch_links.into_iter().enumerate() {
+                // Stop if the budget is exceeded. The links fetched to check degrees are counted
+                // in frontier order as well, so the result does not depend on the threads either.
+                *fetched += links.count + avoid.charge_degree_checks(&links.degree_checked);
+                if limits.budget_exceeded(bfs.visited.len() + other_visited, *fetched) {
+                    unexpanded = Some(batch_index * batch_size + i);
+                    break 'level;
+                }
+
+                for linked in links.allowed {
+                    // Only consider if it has not been visited yet.
+                    if bfs.visited.insert(linked) {
+                        next.push(linked);
+                    }
+                }
+            }
+        }
+
+        // On exceeding the budget, keep the pages that were not expanded after all in the
+        // frontier, along with the ones found so far, to report the progress of the search.
+        if let Some(i) = unexpanded {
+            let frontier = bfs.levels.last_mut().unwrap();
+            frontier.drain(..i);
+            frontier.append(&mut next);
+            return Ok(false);
+        }
+        bfs.levels.push(next);
+        Ok(true)
+    }
+
+    /// Expand the direction of a bidirectional BFS that has the smallest frontier by one level,
+    /// leaving out avoided pages. Returns false if the search exceeded its budget, in which case
+    /// the level is not completed.
+    fn expand_level(
+        &self,
+        txn: &RoTxn<'_>,
+        search: &mut BidirectionalBfs,
+        avoid: &Avoid,
+        limits: &SearchLimits,
+    ) -> Result<bool> {
+        // Take the direction that has the smallest frontier (for efficiency).
+        let is_forward = search.forward.frontier().len() < search.backward.frontier().len();
+        let (bfs, other) = if is_forward {
+            (&mut search.forward, &search.backward)
+        } else {
+            (&mut search.backward, &search.forward)
+        };
+        if !self.expand_bfs(txn, bfs, Some(other), avoid, &mut search.fetched, limits)? {
+            return Ok(false);
+        }
+
+        // The pages of the new level that have been visited by the other direction are overlap.
+        search.overlapping.extend(
+            bfs.frontier()
+                .iter()
+                .filter(|&&page| other.visited.contains(page)),
+        );
+
+        // Increment search depth.
+        if is_forward {
+            search.forward_depth += 1;
+        } else {
+            search.backward_depth += 1;
+        }
+        Ok(true)
+    }
+
+    /// Get the shortest paths between two pages that satisfy the constraints. Returns a
+    /// `SearchError` if the search is cancelled or its deadline passes before it completes. If the
+    /// search exceeds its budget, no paths are returned, but the progress of the search is
+    /// reported instead. Paths via other pages are searched per segment between consecutive
+    /// pages, each with the full budget.
+    pub fn get_shortest_paths(
+        &self,
+        source: PageId,
+        target: PageId,
+        constraints: &PathConstraints,
+        limits: &SearchLimits,
+    ) -> Result<Paths<'_>> {
         let txn = self.read_txn()?;
 
         // Follow any redirects and report whether they were redirects.
@@ -30,119 +551,154 @@ impl Database {
         let (target, target_is_redirect) = self
             .get_redirect(&txn, target)?
             .map_or((target, false), |new_target| (new_target, true));
+        let avoid = self.resolve_avoid(&txn, constraints)?;
 
-        // We run BFS in both directions, so we store two queues.
-        let mut forward_queue = VecDeque::from([source]);
-        let mut backward_queue = VecDeque::from([target]);
-
-        // We store the predecessors of each page on the shortest path to the source/target, so that
-        // we can extract the paths later. Only the source and target can have an empty set.
-        let mut forward_predecessors: HashMap<PageId, HashSet<PageId>> =
-            HashMap::from([(source, HashSet::new())]);
-        let mut backward_predecessors: HashMap<PageId, HashSet<PageId>> =
-            HashMap::from([(target, HashSet::new())]);
+        // Search the segments between consecutive pages, until a segment has no paths.
+        let mut pages = vec![source];
+        for &page in &constraints.via {
+            pages.push(self.get_redirect(&txn, page)?.unwrap_or(page));
+        }
+        pages.push(target);
+        let mut segments = Vec::new();
+        let mut incomplete = None;
+        for endpoints in pages.windows(2) {
+            let segment = self.get_segment(
+                &txn,
+                endpoints[0],
+                endpoints[1],
+                &avoid,
+                constraints.undirected,
+                limits,
+            )?;
+            match segment {
+                Ok(segment) if segment.count > 0 => segments.push(segment),
+                Ok(_) => break,
+                Err(progress) => {
+                    incomplete = Some(progress);
+                    break;
+                }
+            }
+        }
+        if segments.len() < pages.len() - 1 {
+            segments.clear(); // there are no paths
+        }
 
-        // The overlap between the currently visited pages in the forward and backward directions.
-        // If there is overlap, it means we have found the shortest path(s).
-        let mut overlapping: HashSet<PageId> = HashSet::new();
+        // Combine the segments. There are as many paths as combinations of their paths.
+        let mut links: HashMap<PageId, HashSet<PageId>> = HashMap::new();
+        for segment in &segments {
+            for (page, targets) in &segment.links {
+                links.entry(*page).or_default().extend(targets);
+            }
+        }
+        let length = segments.iter().map(|segment| segment.length).sum();
+        let count = if segments.is_empty() {
+            0
+        } else {
+            segments
+                .iter()
+                .map(|segment| segment.count)
+                .fold(1, u64::saturating_mul)
+        };
+        if constraints.via.is_empty() {
+            segments.clear(); // the links are those of the only segment
+        }
 
-        // Keep track of the depth of the BFS of both directions.
-        let mut forward_depth = 0;
-        let mut backward_depth = 0;
+        // Cap the counts to the largest integer JavaScript clients can represent exactly.
+        let count_is_approximate = count > MAX_EXACT_COUNT;
+        let count = count.min(MAX_EXACT_COUNT);
+        for segment in &mut segments {
+            segment.count = segment.count.min(MAX_EXACT_COUNT);
+        }
 
-        // Skip BFS if the source and target are the same.
-        if source == target {
-            overlapping.insert(source);
+        // Look up the titles of all pages involved, if the database contains any.
+        let mut titles = HashMap::new();
+        for page in [source, target].into_iter().chain(links.keys().copied()) {
+            if let Some(title) = self.get_title(&txn, page)? {
+                titles.insert(page, title);
+            }
         }
 
-        // Take BFS steps until either the two directions meet, or all possible paths are depleted.
-        while overlapping.is_empty() && !forward_queue.is_empty() && !backward_queue.is_empty() {
-            let mut new_predecessors: HashMap<PageId, HashSet<PageId>> = HashMap::new();
-
-            // Take the direction that has the shortest queue (for efficiency).
-            if forward_queue.len() < backward_queue.len() {
-                // We pop the front of the queue as many times as the queue is long. The queue may grow
-                // during the loop, but we only consider the pages that were in the queue at the start.
-                for _ in 0..forward_queue.len() {
-                    // Forward queue cannot be empty by the while-loop guard.
-                    let source = forward_queue.pop_front().unwrap();
-
-                    // Consider all outgoing links of the source page.
-                    for target in self.get_outgoing_links(&txn, source)? {
-                        // Only consider if it has not been visited yet.
-                        if !forward_predecessors.contains_key(&target) {
-                            forward_queue.push_back(target);
-
-                            // Mark the target as a predecessor of the source.
-                            new_predecessors.entry(target).or_default().insert(source);
-
-                            // If the target has been visited by the backward BFS, we have found overlap.
-                            if backward_predecessors.contains_key(&target) {
-                                overlapping.insert(target);
-                            }
-                        }
-                    }
-                }
+        // Release the read transaction.
+        txn.commit()?;
 
-                // Insert newly found predecessors into the predecessors map. This is done after
-                // the loop, because we only want to mark them as visited after this iteration.
-                for (child, predecessors) in new_predecessors {
-                    for predecessor in predecessors {
-                        forward_predecessors
-                            .entry(child)
-                            .or_default()
-                            .insert(predecessor);
-                    }
-                }
+        Ok(Paths {
+            source,
+            source_is_redirect,
+            target,
+            target_is_redirect,
+            links,
+            segments,
+            titles,
+            language_code: &self.metadata.language_code,
+            date_code: &self.metadata.date_code,
+            length,
+            count,
+            count_is_approximate,
+            incomplete,
+        })
+    }
 
-                // Increment search depth.
-                forward_depth += 1;
-            } else {
-                // We pop the front of the queue as many times as the queue is long. The queue may grow
-                // during the loop, but we only consider the pages that were in the queue at the start.
-                for _ in 0..backward_queue.len() {
-                    // Backward queue cannot be empty by the while-loop guard.
-                    let target = backward_queue.pop_front().unwrap();
-
-                    // Consider all incoming links of the target page.
-                    for source in self.get_incoming_links(&txn, target)? {
-                        // Only consider if it has not been visited yet.
-                        if !backward_predecessors.contains_key(&source) {
-                            backward_queue.push_back(source);
-
-                            // Mark the source as a predecessor of the target.
-                            new_predecessors.entry(source).or_default().insert(target);
-
-                            // If the source has been visited by the forward BFS, we have found overlap.
-                            if forward_predecessors.contains_key(&source) {
-                                overlapping.insert(source);
-                            }
-                        }
-                    }
-                }
+    /// Follow the redirects of the avoided pages.
+    fn resolve_avoid(&self, txn: &RoTxn<'_>, constraints: &PathConstraints) -> Result<Avoid> {
+        let mut pages = HashSet::new();
+        for &page in &constraints.avoid {
+            pages.insert(self.get_redirect(txn, page)?.unwrap_or(page));
+        }
+        Ok(Avoid {
+            pages,
+            max_in_degree: constraints.max_in_degree,
+            max_out_degree: constraints.max_out_degree,
+            degree_checks: Mutex::default(),
+        })
+    }
 
-                // Insert newly found predecessors into the predecessors map. This is done after
-                // the loop, because we only want to mark them as visited after this iteration.
-                for (child, predecessors) in new_predecessors {
-                    for predecessor in predecessors {
-                        backward_predecessors
-                            .entry(child)
-                            .or_default()
-                            .insert(predecessor);
-                    }
-                }
+    /// Get the shortest paths between two pages, of which any redirects were followed already.
+    /// Returns the progress of the search instead if it exceeded its budget.
+    fn get_segment(
+        &self,
+        txn: &RoTxn<'_>,
+        source: PageId,
+        target: PageId,
+        avoid: &Avoid,
+        undirected: bool,
+        limits: &SearchLimits,
+    ) -> Result<Result<PathSegment, SearchProgress>> {
+        let cancellation = &limits.cancellation;
+
+        // We run BFS in both directions. Only the visited pages of each level are stored during
+        // the search, the predecessors on the shortest paths are reconstructed afterwards.
+        let mut search = BidirectionalBfs::new(source, target, undirected);
 
-                // Increment search depth.
-                backward_depth += 1;
+        // Take BFS steps until either the two directions meet, or all possible paths are depleted.
+        while search.overlapping.is_empty() && !search.is_exhausted() {
+            if !self.expand_level(txn, &mut search, avoid, limits)? {
+                return Ok(Err(search.progress()));
             }
         }
+        let BidirectionalBfs {
+            forward,
+            backward,
+            forward_depth,
+            backward_depth,
+            overlapping,
+            ..
+        } = search;
 
-        // Release the read transaction.
-        txn.commit()?;
+        // Reconstruct the predecessors of the pages on the shortest paths, in both directions.
+        let forward_direction = forward.direction.reversed();
+        let forward_predecessors = forward.predecessors(&overlapping, |page| {
+            cancellation.check()?;
+            self.get_linked_pages(txn, page, forward_direction)
+        })?;
+        let backward_direction = backward.direction.reversed();
+        let backward_predecessors = backward.predecessors(&overlapping, |page| {
+            cancellation.check()?;
+            self.get_linked_pages(txn, page, backward_direction)
+        })?;
 
         // Extract the number of paths and links from the predecessor maps.
         let mut links: HashMap<PageId, HashSet<PageId>> = HashMap::new();
-        let mut count = 0;
+        let mut count: u64 = 0;
         let mut forward_memory = HashMap::new();
         let mut backward_memory = HashMap::new();
         for page in overlapping {
@@ -164,54 +720,1380 @@ impl Database {
                     links.entry(source).or_default().insert(target);
                 },
             );
-            count += forward_count * backward_count;
+            count = count.saturating_add(forward_count.saturating_mul(backward_count));
         }
 
-        Ok(Paths {
+        Ok(Ok(PathSegment {
             source,
-            source_is_redirect,
             target,
-            target_is_redirect,
             links,
-            language_code: &self.metadata.language_code,
-            date_code: &self.metadata.date_code,
             length: if count != 0 {
                 forward_depth + backward_depth
             } else {
                 0
             },
             count,
+        }))
+    }
+}
+
+impl Database {
+    /// Get the simple paths between two pages that are at most `max_extra_length` links longer
+    /// than the shortest paths, ordered by length and then by their page ids. The bidirectional
+    /// BFS is continued beyond the shortest length, after which the paths of each length are
+    /// enumerated depth-first from the source, skipping pages that cannot reach the target in time.
+    /// At most `MAX_NEAR_PATHS` paths are enumerated, shortest first, after which the count is
+    /// approximate. Paths via other pages are not supported.
+    pub fn get_near_shortest_paths(
+        &self,
+        source: PageId,
+        target: PageId,
+        max_extra_length: u32,
+        constraints: &PathConstraints,
+        limits: &SearchLimits,
+        selection: PathSelection,
+    ) -> Result<PathList<'_>> {
+        ensure!(
+            constraints.via.is_empty(),
+            "paths longer than the shortest cannot be required to go via other pages"
+        );
+        let txn = self.read_txn()?;
+
+        // Follow any redirects and report whether they were redirects.
+        let (source, source_is_redirect) = self
+            .get_redirect(&txn, source)?
+            .map_or((source, false), |new_source| (new_source, true));
+        let (target, target_is_redirect) = self
+            .get_redirect(&txn, target)?
+            .map_or((target, false), |new_target| (new_target, true));
+        let avoid = self.resolve_avoid(&txn, constraints)?;
+
+        // Take BFS steps until the two directions meet, and then until their depths add up to the
+        // maximum length, or all possible paths are depleted.
+        let mut search = BidirectionalBfs::new(source, target, constraints.undirected);
+        let mut shortest_length = None;
+        let mut budget_exceeded = false;
+        while !search.is_exhausted() {
+            let length = search.forward_depth + search.backward_depth;
+            if !search.overlapping.is_empty() {
+                let shortest_length = *shortest_length.get_or_insert(length);
+                if length >= shortest_length + max_extra_length {
+                    break;
+                }
+            }
+            if !self.expand_level(&txn, &mut search, &avoid, limits)? {
+                budget_exceeded = true;
+                break;
+            }
+        }
+        if shortest_length.is_none() && !search.overlapping.is_empty() {
+            shortest_length = Some(search.forward_depth + search.backward_depth);
+        }
+
+        // Enumerate the paths, unless the search was stopped early or no paths exist.
+        let incomplete = budget_exceeded.then(|| search.progress());
+        let (mut paths, count_is_approximate) = match (shortest_length, &incomplete) {
+            (Some(shortest_length), None) => {
+                search
+                    .forward
+                    .levels
+                    .iter_mut()
+                    .for_each(|l| l.sort_unstable());
+                search
+                    .backward
+                    .levels
+                    .iter_mut()
+                    .for_each(|l| l.sort_unstable());
+                self.enumerate_paths(
+                    &txn,
+                    &search,
+                    limits,
+                    shortest_length..=shortest_length + max_extra_length,
+                )?
+            }
+            _ => (Vec::new(), false),
+        };
+        paths.sort_unstable_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+
+        let mut counts_by_length = BTreeMap::new();
+        for path in &paths {
+            *counts_by_length
+                .entry(u32::try_from(path.len() - 1)?)
+                .or_default() += 1;
+        }
+        let count = paths.len() as u64;
+        let paths: Vec<Vec<PageId>> = match selection {
+            PathSelection::Range { offset, limit } => paths
+                .into_iter()
+                .skip(usize::try_from(offset).unwrap_or(usize::MAX))
+                .take(limit)
+                .collect(),
+            PathSelection::Sample { size, seed } => sample_ranks(count, size, seed)
+                .into_iter()
+                .map(|rank| std::mem::take(&mut paths[usize::try_from(rank).unwrap()]))
+                .collect(),
+        };
+
+        // Look up the titles of the pages on the listed paths, if the database contains any.
+        let mut titles = HashMap::new();
+        for &page in [source, target].iter().chain(paths.iter().flatten()) {
+            if let Some(title) = self.get_title(&txn, page)? {
+                titles.insert(page, title);
+            }
+        }
+
+        // Release the read transaction.
+        txn.commit()?;
+
+        Ok(PathList {
+            source,
+            source_is_redirect,
+            target,
+            target_is_redirect,
+            paths,
+            titles,
+            language_code: &self.metadata.language_code,
+            date_code: &self.metadata.date_code,
+            length: shortest_length.filter(|_| count > 0).unwrap_or(0),
+            count,
+            count_is_approximate,
+            counts_by_length,
+            incomplete,
+        })
+    }
+
+    /// Enumerate the simple paths from the source to the target of a completed bidirectional BFS,
+    /// with lengths in the given range. The paths of each length are enumerated depth-first in the
+    /// order of page ids, from the shortest length up, so shorter paths are never left out in
+    /// favour of longer ones. Also returns whether the enumeration was stopped early, because of
+    /// `MAX_NEAR_PATHS` or the search budget.
+    fn enumerate_paths(
+        &self,
+        txn: &RoTxn<'_>,
+        search: &BidirectionalBfs,
+        limits: &SearchLimits,
+        lengths: RangeInclusive<u32>,
+    ) -> Result<(Vec<Vec<PageId>>, bool)> {
+        let source = search.forward.levels[0][0];
+        let target = search.backward.levels[0][0];
+        if source == target {
+            return Ok((vec![vec![source]], false)); // a simple path cannot return to the source
+        }
+
+        // Outgoing links are cached, since pages can be on many paths. Returns `None` if the
+        // search budget is exceeded.
+        let mut outgoing: HashMap<PageId, Vec<PageId>> = HashMap::new();
+        let mut fetched = search.fetched;
+        let mut get_outgoing = |page: PageId| -> Result<Option<Vec<PageId>>> {
+            if let Some(links) = outgoing.get(&page) {
+                return Ok(Some(links.clone()));
+            }
+            limits.cancellation.check()?;
+            let links = self.get_linked_pages(txn, page, search.forward.direction)?;
+            fetched += links.len();
+            let visited = search.forward.visited.len() + search.backward.visited.len();
+            if limits.budget_exceeded(visited + outgoing.len(), fetched) {
+                return Ok(None);
+            }
+            outgoing.insert(page, links.clone());
+            Ok(Some(links))
+        };
+
+        // The outgoing links of each page on the current path are kept on a stack, along with the
+        // index of the next link to follow. Once stopped early, the paths found so far are kept.
+        let mut paths = Vec::new();
+        for length in lengths {
+            // Whether a page at some position of a path can still reach the target in time. Pages
+            // not visited backward are further from the target than the backward depth, and must
+            // have been visited forward to be on any path.
+            let can_reach_target = |page: PageId, position: u32| match search.backward.depth(page) {
+                Some(depth) => position + depth <= length,
+                None => {
+                    !search.backward.frontier().is_empty()
+                        && search.forward.visited.contains(page)
+                        && position + search.backward_depth < length
+                }
+            };
+
+            let mut path = vec![source];
+            let Some(links) = get_outgoing(source)? else {
+                return Ok((paths, true));
+            };
+            let mut stack = vec![(links, 0)];
+            while let Some((links, index)) = stack.last_mut() {
+                limits.cancellation.check()?;
+                let Some(&page) = links.get(*index) else {
+                    stack.pop();
+                    path.pop();
+                    continue;
+                };
+                *index += 1;
+
+                let position = u32::try_from(path.len())?;
+                if path.contains(&page) || !can_reach_target(page, position) {
+                    continue;
+                }
+                if page == target {
+                    // Shorter paths were enumerated already.
+                    if position == length {
+                        let mut found = path.clone();
+                        found.push(page);
+                        paths.push(found);
+                        if paths.len() >= MAX_NEAR_PATHS {
+                            return Ok((paths, true));
+                        }
+                    }
+                    continue;
+                }
+                let Some(links) = get_outgoing(page)? else {
+                    return Ok((paths, true));
+                };
+                path.push(page);
+                stack.push((links, 0));
+            }
+        }
+        Ok((paths, false))
+    }
+}
+
+/// The number of pages at each distance from a page.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Neighborhood<'a> {
+    source: PageId,
+    source_is_redirect: bool,
+    levels: Vec<NeighborhoodLevel>, // from distance 1, leaving out distances without pages
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    titles: HashMap<PageId, String>, // only for databases containing titles, e.g. imported ones
+    language_code: &'a str,
+    date_code: &'a str,
+    incomplete: bool, // if so, the search exceeded its budget before reaching the maximum depth
+}
+
+/// The pages at a single distance from the source of a neighborhood.
+#[derive(Debug, Serialize)]
+pub struct NeighborhoodLevel {
+    depth: u32,
+    count: usize,
+    pages: Vec<PageId>, // the ones with the lowest ids, up to the limit
+}
+
+impl Database {
+    /// Get the number of pages at each distance from a page up to a maximum depth, following links
+    /// in the given direction, along with up to `limit` of the pages at each distance. Returns a
+    /// `SearchError` if the search is cancelled or its deadline passes before it completes. If the
+    /// search exceeds its budget, only the distances that were completed are returned.
+    pub fn get_neighborhood(
+        &self,
+        source: PageId,
+        direction: LinkDirection,
+        max_depth: u32,
+        limit: usize,
+        limits: &SearchLimits,
+    ) -> Result<Neighborhood<'_>> {
+        let txn = self.read_txn()?;
+
+        // Follow any redirect and report whether it was a redirect.
+        let (source, source_is_redirect) = self
+            .get_redirect(&txn, source)?
+            .map_or((source, false), |new_source| (new_source, true));
+
+        // Take BFS steps until the maximum depth, or until no pages are left. Each level is
+        // summarized once it is complete, since an incomplete level replaces the frontier.
+        let mut bfs = Bfs::new(source, direction);
+        let mut levels = Vec::new();
+        let mut fetched = 0;
+        let mut incomplete = false;
+        for depth in 1..=max_depth {
+            if !self.expand_bfs(
+                &txn,
+                &mut bfs,
+                None,
+                &Avoid::default(),
+                &mut fetched,
+                limits,
+            )? {
+                incomplete = true;
+                break;
+            }
+            let frontier = bfs.frontier();
+            if frontier.is_empty() {
+                break;
+            }
+            // List the pages with the lowest ids, without sorting the whole level.
+            let mut pages = frontier.to_vec();
+            if pages.len() > limit {
+                pages.select_nth_unstable(limit);
+                pages.truncate(limit);
+            }
+            pages.sort_unstable();
+            levels.push(NeighborhoodLevel {
+                depth,
+                count: frontier.len(),
+                pages,
+            });
+        }
+
+        // Look up the titles of the listed pages, if the database contains any.
+        let mut titles = HashMap::new();
+        let listed = levels.iter().flat_map(|level| level.pages.iter().copied());
+        for page in std::iter::once(source).chain(listed) {
+            if let Some(title) = self.get_title(&txn, page)? {
+                titles.insert(page, title);
+            }
+        }
+
+        // Release the read transaction.
+        txn.commit()?;
+
+        Ok(Neighborhood {
+            source,
+            source_is_redirect,
+            levels,
+            titles,
+            language_code: &self.metadata.language_code,
+            date_code: &self.metadata.date_code,
+            incomplete,
+        })
+    }
+}
+
+/// The subgraph induced by the pages around some center pages, for visualizing their local
+/// link structure.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Subgraph<'a> {
+    centers: Vec<PageId>,
+    nodes: Vec<SubgraphNode>,     // ordered by rank
+    edges: Vec<(PageId, PageId)>, // the links between the nodes
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    titles: HashMap<PageId, String>, // only for databases containing titles, e.g. imported ones
+    language_code: &'a str,
+    date_code: &'a str,
+    count: usize, // pages within the radius, of which the highest ranked are the nodes
+    incomplete: bool, // if so, the budget ran out before reaching the radius or ranking the pages
+}
+
+#[derive(Debug, Serialize)]
+pub struct SubgraphNode {
+    id: PageId,
+    depth: u32,    // distance from the nearest center page
+    degree: usize, // number of links from and to the page
+}
+
+impl Database {
+    /// Get the subgraph induced by the pages within a radius of the center pages, following links
+    /// in the given direction. If there are more than `max_nodes` pages, the center pages are
+    /// kept and the others are ranked by their distance, and then by their degree. Returns a
+    /// `SearchError` if the search is cancelled or its deadline passes before it completes. If the
+    /// search exceeds its budget, the subgraph is limited to the distances that were completed.
+    pub fn get_subgraph(
+        &self,
+        centers: &[PageId],
+        direction: LinkDirection,
+        radius: u32,
+        max_nodes: usize,
+        limits: &SearchLimits,
+    ) -> Result<Subgraph<'_>> {
+        let txn = self.read_txn()?;
+        let cancellation = &limits.cancellation;
+
+        // Follow any redirects of the center pages, which make up the root level.
+        let mut centers_resolved = Vec::new();
+        for &page in centers {
+            centers_resolved.push(self.get_redirect(&txn, page)?.unwrap_or(page));
+        }
+        let centers = centers_resolved;
+        ensure!(
+            !centers.is_empty(),
+            "a subgraph needs at least one center page"
+        );
+        ensure!(
+            centers.len() <= max_nodes,
+            "a subgraph cannot have more center pages than nodes"
+        );
+        let mut bfs = Bfs::new(centers[0], direction);
+        for &page in &centers[1..] {
+            if bfs.visited.insert(page) {
+                bfs.levels[0].push(page);
+            }
+        }
+
+        // Take BFS steps until the radius, or until no pages are left. An incomplete level
+        // replaces the frontier, so it is restored to only keep the completed levels.
+        let mut fetched = 0;
+        let mut incomplete = false;
+        for _ in 0..radius {
+            if bfs.frontier().is_empty() {
+                break;
+            }
+            let frontier = bfs.frontier().to_vec();
+            if !self.expand_bfs(
+                &txn,
+                &mut bfs,
+                None,
+                &Avoid::default(),
+                &mut fetched,
+                limits,
+            )? {
+                *bfs.levels.last_mut().unwrap() = frontier;
+                incomplete = true;
+                break;
+            }
+        }
+
+        // Rank the pages level by level, and by degree within a level. Of the first level that does
+        // not fit entirely, only a sample of pages spread evenly over its page ids is ranked if it
+        // is much larger than the remaining capacity, and the levels beyond it are left out without
+        // looking up their degrees. Looking up degrees counts towards the budget, and once it is
+        // exceeded, the remaining levels are left out as well. The center pages are always kept, so
+        // the subgraph is never empty.
+        let count = bfs.levels.iter().map(Vec::len).sum();
+        let get_degree = |page: PageId| -> Result<usize> {
+            cancellation.check()?;
+            Ok(self.get_outgoing_links(&txn, page)?.len()
+                + self.get_incoming_links(&txn, page)?.len())
+        };
+        let mut nodes = Vec::new();
+        'levels: for (depth, level) in (0..).zip(&bfs.levels) {
+            let remaining = max_nodes - nodes.len();
+            let mut level_nodes = Vec::new();
+            for page in spread_sample(level, remaining.saturating_mul(SUBGRAPH_RANK_FACTOR)) {
+                let degree = get_degree(page)?;
+                fetched += degree;
+                if depth > 0 && limits.budget_exceeded(count, fetched) {
+                    incomplete = true;
+                    break 'levels;
+                }
+                level_nodes.push(SubgraphNode {
+                    id: page,
+                    depth,
+                    degree,
+                });
+            }
+            level_nodes.sort_unstable_by_key(|node| (std::cmp::Reverse(node.degree), node.id));
+            level_nodes.truncate(remaining);
+            nodes.append(&mut level_nodes);
+            if nodes.len() == max_nodes {
+                break;
+            }
+        }
+
+        // Collect the links between the nodes.
+        let node_ids: HashSet<PageId> = nodes.iter().map(|node| node.id).collect();
+        let mut edges = Vec::new();
+        for node in &nodes {
+            cancellation.check()?;
+            for linked in self.get_outgoing_links(&txn, node.id)? {
+                if node_ids.contains(&linked) {
+                    edges.push((node.id, linked));
+                }
+            }
+        }
+        edges.sort_unstable();
+
+        // Look up the titles of the nodes, if the database contains any.
+        let mut titles = HashMap::new();
+        for node in &nodes {
+            if let Some(title) = self.get_title(&txn, node.id)? {
+                titles.insert(node.id, title);
+            }
+        }
+
+        // Release the read transaction.
+        txn.commit()?;
+
+        Ok(Subgraph {
+            centers,
+            nodes,
+            edges,
+            titles,
+            language_code: &self.metadata.language_code,
+            date_code: &self.metadata.date_code,
+            count,
+            incomplete,
         })
     }
 }
 
+impl Database {
+    /// Fetch the pages linked in the given direction from each of the given pages, leaving out the
+    /// ones that are not allowed by the filter.
+    fn fetch_unvisited(
+        &self,
+        txn: &RoTxn<'_>,
+        pages: &[PageId],
+        direction: LinkDirection,
+        filter: &LevelFilter<'_>,
+        cancellation: &Cancellation,
+    ) -> Result<Vec<FetchedLinks>> {
+        pages
+            .iter()
+            .map(|&page| {
+                cancellation.check()?;
+                let linked_pages = self.get_linked_pages(txn, page, direction)?;
+                let mut allowed = Vec::new();
+                let mut degree_checked = Vec::new();
+                for &linked in &linked_pages {
+                    if filter.allows(self, txn, linked, &mut degree_checked)? {
+                        allowed.push(linked);
+                    }
+                }
+                Ok(FetchedLinks {
+                    count: linked_pages.len(),
+                    allowed,
+                    degree_checked,
+                })
+            })
+            .collect()
+    }
+
+    /// Get the pages linked from or to a page. In both directions, the outgoing and incoming links
+    /// are combined without duplicates.
+    fn get_linked_pages(
+        &self,
+        txn: &RoTxn<'_>,
+        page: PageId,
+        direction: LinkDirection,
+    ) -> Result<Vec<PageId>> {
+        match direction {
+            LinkDirection::Outgoing => self.get_outgoing_links(txn, page),
+            LinkDirection::Incoming => self.get_incoming_links(txn, page),
+            LinkDirection::Both => {
+                let mut linked_pages = self.get_outgoing_links(txn, page)?;
+                linked_pages.extend(self.get_incoming_links(txn, page)?);
+                linked_pages.sort_unstable();
+                linked_pages.dedup();
+                Ok(linked_pages)
+            }
+        }
+    }
+}
+
 /// Count the number of paths from a source page to a target page. To avoid duplicate work, it uses
 /// (and appends to) a memory which stores results. All traversed edges are reported to a function.
-/// All paths are assumed to end up in the target, else it panics or has undefined behaviour.
+/// All paths are assumed to end up in the target, else it panics or has undefined behaviour. The
+/// pages are traversed depth-first with an explicit stack, so deep graphs cannot overflow the call
+/// stack, and counts saturate at `u64::MAX`.
 fn count_paths(
     source: PageId,
     target: PageId,
     edges: &HashMap<PageId, HashSet<PageId>>,
-    result_memory: &mut HashMap<PageId, u32>,
+    result_memory: &mut HashMap<PageId, u64>,
     report_edge: &mut impl FnMut(PageId, PageId),
-) -> u32 {
+) -> u64 {
     if source == target {
         return 1;
     }
 
-    edges
-        .get(&source)
-        .unwrap()
-        .iter()
-        .map(|&subtarget| {
-            report_edge(source, subtarget);
-            if let Some(memoized) = result_memory.get(&subtarget) {
-                *memoized
+    // Pages are pushed once to visit their subtargets, and once more to sum their counts after
+    // the counts of all their subtargets are known.
+    let mut stack = vec![(source, false)];
+    while let Some((page, is_visited)) = stack.pop() {
+        let subtargets = edges.get(&page).unwrap();
+        if is_visited {
+            let result = subtargets
+                .iter()
+                .map(|subtarget| {
+                    if *subtarget == target {
+                        1
+                    } else {
+                        result_memory[subtarget]
+                    }
+                })
+                .fold(0, u64::saturating_add);
+            result_memory.insert(page, result);
+        } else if page == source || !result_memory.contains_key(&page) {
+            stack.push((page, true));
+            for &subtarget in subtargets {
+                report_edge(page, subtarget);
+                if subtarget != target && !result_memory.contains_key(&subtarget) {
+                    stack.push((subtarget, false));
+                }
+            }
+        }
+    }
+    result_memory[&source]
+}
+
+/// Which of the shortest paths to list, in the order of their page ids.
+#[derive(Debug, Clone, Copy)]
+pub enum PathSelection {
+    /// The paths at an offset in the order.
+    Range { offset: u64, limit: usize },
+    /// A uniformly random sample of distinct paths, which is reproducible given the same seed.
+    Sample { size: usize, seed: u64 },
+}
+
+/// A list of concrete shortest paths between two pages, as sequences of page ids.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PathList<'a> {
+    source: PageId,
+    source_is_redirect: bool,
+    target: PageId,
+    target_is_redirect: bool,
+    paths: Vec<Vec<PageId>>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    titles: HashMap<PageId, String>, // only for databases containing titles, e.g. imported ones
+    language_code: &'a str,
+    date_code: &'a str,
+    length: u32,
+    count: u64,
+    count_is_approximate: bool, // if so, there are more paths than the count
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    counts_by_length: BTreeMap<u32, u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    incomplete: Option<SearchProgress>, // only if the search exceeded its budget
+}
+
+impl<'a> Paths<'a> {
+    /// List a selection of the paths. Paths are ranked by their page ids, and are looked up by
+    /// rank using the number of paths from each page to the target. Paths via other pages are
+    /// combinations of the paths of their segments, ranked by the first segment first. If the
+    /// count saturated, only the paths with the lowest ranks can be listed.
+    pub fn into_list(self, selection: PathSelection) -> PathList<'a> {
+        let segments: Vec<SegmentIndex> = if self.count == 0 {
+            Vec::new()
+        } else if self.segments.is_empty() {
+            vec![SegmentIndex::new(self.source, self.target, self.links)]
+        } else {
+            self.segments
+                .into_iter()
+                .map(|segment| SegmentIndex::new(segment.source, segment.target, segment.links))
+                .collect()
+        };
+        let total = if segments.is_empty() {
+            0
+        } else {
+            segments
+                .iter()
+                .map(|segment| segment.total)
+                .fold(1, u64::saturating_mul)
+        };
+
+        let ranks: Vec<u64> = match selection {
+            PathSelection::Range { offset, limit } => (offset..total).take(limit).collect(),
+            PathSelection::Sample { size, seed } => sample_ranks(total, size, seed),
+        };
+        let paths: Vec<Vec<PageId>> = ranks
+            .into_iter()
+            .map(|mut rank| {
+                let mut segment_ranks = vec![0; segments.len()];
+                for (segment, segment_rank) in segments.iter().zip(&mut segment_ranks).rev() {
+                    *segment_rank = rank % segment.total;
+                    rank /= segment.total;
+                }
+                let mut path = vec![self.source];
+                for (segment, segment_rank) in segments.iter().zip(segment_ranks) {
+                    path.extend(&segment.find_path(segment_rank)[1..]);
+                }
+                path
+            })
+            .collect();
+
+        // Only keep the titles of the pages on the listed paths.
+        let listed: HashSet<PageId> = [self.source, self.target]
+            .into_iter()
+            .chain(paths.iter().flatten().copied())
+            .collect();
+        let mut titles = self.titles;
+        titles.retain(|page, _| listed.contains(page));
+
+        PathList {
+            source: self.source,
+            source_is_redirect: self.source_is_redirect,
+            target: self.target,
+            target_is_redirect: self.target_is_redirect,
+            paths,
+            titles,
+            language_code: self.language_code,
+            date_code: self.date_code,
+            length: self.length,
+            count: self.count,
+            count_is_approximate: self.count_is_approximate,
+            counts_by_length: if self.count > 0 {
+                BTreeMap::from([(self.length, self.count)])
             } else {
-                let result = count_paths(subtarget, target, edges, result_memory, report_edge);
-                result_memory.insert(subtarget, result);
-                result
+                BTreeMap::new()
+            },
+            incomplete: self.incomplete,
+        }
+    }
+}
+
+/// The paths of a segment with the number of paths from each page to its target, to find paths
+/// by rank.
+struct SegmentIndex {
+    source: PageId,
+    target: PageId,
+    successors: HashMap<PageId, Vec<PageId>>, // sorted
+    counts: HashMap<PageId, u64>,
+    total: u64,
+}
+
+impl SegmentIndex {
+    fn new(source: PageId, target: PageId, links: HashMap<PageId, HashSet<PageId>>) -> Self {
+        let mut counts = HashMap::new();
+        let total = count_paths(source, target, &links, &mut counts, &mut |_, _| {});
+        let successors = links
+            .into_iter()
+            .map(|(page, successors)| {
+                let mut successors: Vec<PageId> = successors.into_iter().collect();
+                successors.sort_unstable();
+                (page, successors)
+            })
+            .collect();
+        Self {
+            source,
+            target,
+            successors,
+            counts,
+            total,
+        }
+    }
+
+    /// Find the path with the given rank among the paths from the source to the target, ordered
+    /// by their page ids. At each page, the rank is narrowed down to one of its sorted successors
+    /// using the number of paths from each successor to the target.
+    fn find_path(&self, mut rank: u64) -> Vec<PageId> {
+        let mut path = vec![self.source];
+        let mut page = self.source;
+        while page != self.target {
+            for &successor in &self.successors[&page] {
+                let count = if successor == self.target {
+                    1
+                } else {
+                    self.counts[&successor]
+                };
+                if rank < count {
+                    page = successor;
+                    break;
+                }
+                rank -= count;
+            }
+            path.push(page);
+        }
+        path
+    }
+}
+
+/// Sample pages spread evenly over the page ids, or all pages if there are not more than the size.
+fn spread_sample(pages: &[PageId], size: usize) -> Vec<PageId> {
+    let mut sample = pages.to_vec();
+    if sample.len() > size {
+        sample.sort_unstable();
+        sample = (0..size).map(|i| sample[i * sample.len() / size]).collect();
+    }
+    sample
+}
+
+/// Sample distinct ranks uniformly from `0..total` (Floyd's algorithm), in ascending order.
+fn sample_ranks(total: u64, size: usize, seed: u64) -> Vec<u64> {
+    let size = u64::try_from(size).unwrap_or(u64::MAX).min(total);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut ranks = BTreeSet::new();
+    for upper in total - size..total {
+        let rank = rng.random_range(0..=upper);
+        if !ranks.insert(rank) {
+            ranks.insert(upper);
+        }
+    }
+    ranks.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        database::{Metadata, Mode},
+        import::ImportFiles,
+    };
+    use std::{fmt::Write, fs, path::PathBuf};
+
+    /// Import a database with the given links, in a temporary directory of its own.
+    fn import_graph(
+        name: &str,
+        links: impl IntoIterator<Item = (PageId, PageId)>,
+    ) -> (PathBuf, Database) {
+        let dir =
+            std::env::temp_dir().join(format!("wikipath-search-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let edges = dir.join("edges.tsv");
+        let mut content = String::new();
+        for (source, target) in links {
+            writeln!(content, "{source}\t{target}").unwrap();
+        }
+        fs::write(&edges, content).unwrap();
+
+        let files = ImportFiles {
+            edges,
+            aliases: None,
+            labels: None,
+            delimiter: None,
+        };
+        let metadata = Metadata::new(name, "20260101").unwrap();
+        let tmp_path = dir.join("tmp").join(metadata.to_name());
+        let final_path = dir.join(metadata.to_name());
+        Database::import(&metadata, &files, &tmp_path, &final_path).unwrap();
+        (dir, Database::open(&final_path, Mode::Serve).unwrap())
+    }
+
+    /// Find all shortest paths between two pages by a plain BFS in each direction, in the order of
+    /// their page ids.
+    fn brute_force_paths(
+        links: &[(PageId, PageId)],
+        source: PageId,
+        target: PageId,
+    ) -> Vec<Vec<PageId>> {
+        let mut outgoing: HashMap<PageId, Vec<PageId>> = HashMap::new();
+        let mut incoming: HashMap<PageId, Vec<PageId>> = HashMap::new();
+        for &(a, b) in links {
+            outgoing.entry(a).or_default().push(b);
+            incoming.entry(b).or_default().push(a);
+        }
+        let distances = |from: PageId, linked: &HashMap<PageId, Vec<PageId>>| {
+            let mut distances = HashMap::from([(from, 0)]);
+            let mut frontier = vec![from];
+            while !frontier.is_empty() {
+                let mut next = Vec::new();
+                for page in frontier {
+                    for &other in linked.get(&page).into_iter().flatten() {
+                        if !distances.contains_key(&other) {
+                            distances.insert(other, distances[&page] + 1);
+                            next.push(other);
+                        }
+                    }
+                }
+                frontier = next;
             }
+            distances
+        };
+        let from_source = distances(source, &outgoing);
+        let to_target = distances(target, &incoming);
+        let Some(&length) = from_source.get(&target) else {
+            return Vec::new();
+        };
+
+        let mut paths = vec![vec![source]];
+        for position in 1..=length {
+            let mut next = Vec::new();
+            for path in paths {
+                let mut linked: Vec<_> = outgoing
+                    .get(path.last().unwrap())
+                    .into_iter()
+                    .flatten()
+                    .copied()
+                    .filter(|page| {
+                        from_source.get(page) == Some(&position)
+                            && to_target.get(page) == Some(&(length - position))
+                    })
+                    .collect();
+                linked.sort_unstable();
+                linked.dedup();
+                for page in linked {
+                    let mut path = path.clone();
+                    path.push(page);
+                    next.push(path);
+                }
+            }
+            paths = next;
+        }
+        paths
+    }
+
+    /// List all shortest paths between two pages, in the order of their page ids.
+    fn list_all(paths: Paths<'_>) -> Vec<Vec<PageId>> {
+        paths
+            .into_list(PathSelection::Range {
+                offset: 0,
+                limit: usize::MAX,
+            })
+            .paths
+    }
+
+    #[test]
+    fn cancellation_stops_search() {
+        let cancellation = Cancellation::default();
+        assert_eq!(cancellation.check(), Ok(()));
+        cancellation.clone().cancel();
+        assert_eq!(cancellation.check(), Err(SearchError::Cancelled));
+
+        let expired = Cancellation::with_deadline(Instant::now());
+        assert_eq!(expired.check(), Err(SearchError::TimedOut));
+    }
+
+    #[test]
+    fn counts_paths_without_recursion_or_overflow() {
+        // A chain of pages, each with the previous page as its only predecessor.
+        let chain: HashMap<PageId, HashSet<PageId>> = (1..=100_000)
+            .map(|page| (page, HashSet::from([page - 1])))
+            .collect();
+        let mut edges = 0;
+        let count = count_paths(100_000, 0, &chain, &mut HashMap::new(), &mut |_, _| {
+            edges += 1;
+        });
+        assert_eq!((count, edges), (1, 100_000));
+
+        // Levels of two pages, each with both pages of the previous level as predecessors, which
+        // doubles the number of paths per level.
+        let mut ladder = HashMap::from([(1, HashSet::from([0])), (2, HashSet::from([0]))]);
+        for page in 3..=200_u32 {
+            let previous = 2 * (page.div_ceil(2) - 1);
+            ladder.insert(page, HashSet::from([previous - 1, previous]));
+        }
+        assert_eq!(
+            count_paths(20, 0, &ladder, &mut HashMap::new(), &mut |_, _| {}),
+            1 << 9
+        );
+        assert_eq!(
+            count_paths(200, 0, &ladder, &mut HashMap::new(), &mut |_, _| {}),
+            u64::MAX
+        );
+    }
+
+    #[test]
+    fn lists_shortest_paths_before_longer_ones() {
+        // A single shortest path through a page with a high id, and more longer paths through
+        // pages with lower ids than can be enumerated.
+        let mut links = vec![(1, 100_000), (100_000, 2), (1, 3)];
+        for page in 10..10_010 + 10 {
+            links.extend([(3, page), (page, 2)]);
+        }
+        let (dir, db) = import_graph("near", links);
+
+        let list = db
+            .get_near_shortest_paths(
+                1,
+                2,
+                1,
+                &PathConstraints::default(),
+                &SearchLimits::default(),
+                PathSelection::Range {
+                    offset: 0,
+                    limit: 2,
+                },
+            )
+            .unwrap();
+        assert_eq!(list.length, 2);
+        assert_eq!(list.paths, vec![vec![1, 100_000, 2], vec![1, 3, 10, 2]]);
+        assert_eq!(
+            list.counts_by_length,
+            BTreeMap::from([(2, 1), (3, MAX_NEAR_PATHS as u64 - 1)])
+        );
+        assert!(list.count_is_approximate);
+        drop(list);
+        drop(db);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn lists_paths_by_range_and_sample() {
+        let (dir, db) = import_graph("list", [(1, 2), (1, 3), (1, 4), (2, 5), (3, 5), (4, 5)]);
+        let list = |selection| {
+            let paths = db
+                .get_shortest_paths(1, 5, &PathConstraints::default(), &SearchLimits::default())
+                .unwrap();
+            assert_eq!(paths.count, 3);
+            paths.into_list(selection).paths
+        };
+
+        let all = vec![vec![1, 2, 5], vec![1, 3, 5], vec![1, 4, 5]];
+        assert_eq!(
+            list(PathSelection::Range {
+                offset: 0,
+                limit: 10
+            }),
+            all
+        );
+        assert_eq!(
+            list(PathSelection::Range {
+                offset: 1,
+                limit: 1
+            }),
+            vec![vec![1, 3, 5]]
+        );
+        assert!(list(PathSelection::Range {
+            offset: 3,
+            limit: 10
+        })
+        .is_empty());
+
+        // Samples are distinct paths in the order of their ranks, and reproducible given the seed.
+        let sample = list(PathSelection::Sample { size: 2, seed: 7 });
+        assert_eq!(sample.len(), 2);
+        assert!(sample[0] < sample[1] && sample.iter().all(|path| all.contains(path)));
+        assert_eq!(list(PathSelection::Sample { size: 2, seed: 7 }), sample);
+        assert_eq!(list(PathSelection::Sample { size: 10, seed: 7 }), all);
+        drop(db);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn avoids_pages_and_goes_via_pages() {
+        let (dir, db) = import_graph(
+            "constraints",
+            [
+                (1, 2),
+                (1, 3),
+                (2, 4),
+                (3, 4),
+                (1, 5),
+                (5, 6),
+                (6, 4),
+                (4, 7),
+                (7, 1),
+            ],
+        );
+        let search = |constraints: PathConstraints| {
+            list_all(
+                db.get_shortest_paths(1, 4, &constraints, &SearchLimits::default())
+                    .unwrap(),
+            )
+        };
+
+        assert_eq!(
+            search(PathConstraints::default()),
+            vec![vec![1, 2, 4], vec![1, 3, 4]]
+        );
+        assert_eq!(
+            search(PathConstraints {
+                avoid: vec![2],
+                ..Default::default()
+            }),
+            vec![vec![1, 3, 4]]
+        );
+        assert_eq!(
+            search(PathConstraints {
+                avoid: vec![2, 3],
+                ..Default::default()
+            }),
+            vec![vec![1, 5, 6, 4]]
+        );
+        assert!(search(PathConstraints {
+            avoid: vec![2, 3, 6],
+            ..Default::default()
         })
-        .sum()
+        .is_empty());
+        assert_eq!(
+            search(PathConstraints {
+                via: vec![5],
+                ..Default::default()
+            }),
+            vec![vec![1, 5, 6, 4]]
+        );
+        drop(db);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn skips_hubs_and_counts_their_links_once() {
+        // Paths from 1 to 2 through hub 10, which pages 3 and 5 both link to, or around it.
+        let mut links = vec![
+            (1, 3),
+            (1, 5),
+            (3, 10),
+            (5, 10),
+            (10, 2),
+            (3, 6),
+            (6, 7),
+            (7, 2),
+        ];
+        links.extend((100..198).map(|page| (page, 10)));
+        let (dir, db) = import_graph("hubs", links);
+        let search = |max_in_degree, max_fetched| {
+            let constraints = PathConstraints {
+                max_in_degree,
+                ..Default::default()
+            };
+            let limits = SearchLimits {
+                max_fetched,
+                ..Default::default()
+            };
+            let paths = db.get_shortest_paths(1, 2, &constraints, &limits).unwrap();
+            (paths.incomplete.is_some(), list_all(paths))
+        };
+
+        assert_eq!(
+            search(None, None),
+            (false, vec![vec![1, 3, 10, 2], vec![1, 5, 10, 2]])
+        );
+        assert_eq!(search(Some(50), None), (false, vec![vec![1, 3, 6, 7, 2]]));
+
+        // The 100 incoming links fetched to check the hub count towards the budget, but only once,
+        // although the hub is checked from several pages and in both directions. Other than that,
+        // the search fetches only a few links.
+        assert!(search(Some(50), Some(100)).0);
+        assert_eq!(
+            search(Some(50), Some(150)),
+            (false, vec![vec![1, 3, 6, 7, 2]])
+        );
+        drop(db);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn follows_links_in_either_direction_when_undirected() {
+        // Page 1 reaches page 4 in three links, or in two links against the direction of 3 -> 2.
+        let (dir, db) = import_graph(
+            "undirected",
+            [(1, 2), (3, 2), (3, 4), (2, 5), (5, 6), (6, 4)],
+        );
+        let search = |source, target, undirected| {
+            let constraints = PathConstraints {
+                undirected,
+                ..Default::default()
+            };
+            list_all(
+                db.get_shortest_paths(source, target, &constraints, &SearchLimits::default())
+                    .unwrap(),
+            )
+        };
+
+        assert_eq!(search(1, 4, false), vec![vec![1, 2, 5, 6, 4]]);
+        assert_eq!(search(1, 4, true), vec![vec![1, 2, 3, 4]]);
+
+        // Searching both ways is a search in each direction, which may differ.
+        assert!(search(4, 1, false).is_empty());
+        assert_eq!(search(4, 1, true), vec![vec![4, 3, 2, 1]]);
+        drop(db);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn lists_lowest_pages_of_each_neighborhood_level() {
+        let (dir, db) = import_graph(
+            "neighborhood",
+            [
+                (1, 2),
+                (1, 3),
+                (2, 30),
+                (3, 10),
+                (3, 20),
+                (10, 40),
+                (40, 50),
+            ],
+        );
+        let levels = |source, direction, max_depth, limit| {
+            let neighborhood = db
+                .get_neighborhood(
+                    source,
+                    direction,
+                    max_depth,
+                    limit,
+                    &SearchLimits::default(),
+                )
+                .unwrap();
+            assert!(!neighborhood.incomplete);
+            neighborhood
+                .levels
+                .into_iter()
+                .map(|level| (level.depth, level.count, level.pages))
+                .collect::<Vec<_>>()
+        };
+
+        // The second level is found in the order 30, 10, 20, of which the lowest are listed.
+        assert_eq!(
+            levels(1, LinkDirection::Outgoing, 2, 2),
+            vec![(1, 2, vec![2, 3]), (2, 3, vec![10, 20])]
+        );
+        assert_eq!(
+            levels(1, LinkDirection::Outgoing, 10, 0),
+            vec![
+                (1, 2, vec![]),
+                (2, 3, vec![]),
+                (3, 1, vec![]),
+                (4, 1, vec![])
+            ]
+        );
+        assert_eq!(
+            levels(40, LinkDirection::Incoming, 2, 10),
+            vec![(1, 1, vec![10]), (2, 1, vec![3])]
+        );
+        assert_eq!(
+            levels(10, LinkDirection::Both, 1, 10),
+            vec![(1, 2, vec![3, 40])]
+        );
+        drop(db);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn ranks_subgraph_nodes_by_depth_and_degree() {
+        let (dir, db) = import_graph(
+            "subgraph",
+            [
+                (1, 2),
+                (1, 3),
+                (1, 4),
+                (2, 5),
+                (2, 6),
+                (2, 7),
+                (3, 5),
+                (6, 2),
+            ],
+        );
+        let subgraph = |max_nodes| {
+            let subgraph = db
+                .get_subgraph(
+                    &[1],
+                    LinkDirection::Outgoing,
+                    2,
+                    max_nodes,
+                    &SearchLimits::default(),
+                )
+                .unwrap();
+            assert_eq!(subgraph.count, 7);
+            let nodes = subgraph
+                .nodes
+                .iter()
+                .map(|node| (node.id, node.depth, node.degree))
+                .collect::<Vec<_>>();
+            (nodes, subgraph.edges)
+        };
+
+        // Pages closer to the center come first, and pages with more links within a level.
+        assert_eq!(
+            subgraph(3),
+            (vec![(1, 0, 3), (2, 1, 5), (3, 1, 2)], vec![(1, 2), (1, 3)])
+        );
+        assert_eq!(
+            subgraph(6),
+            (
+                vec![
+                    (1, 0, 3),
+                    (2, 1, 5),
+                    (3, 1, 2),
+                    (4, 1, 1),
+                    (5, 2, 2),
+                    (6, 2, 2)
+                ],
+                vec![(1, 2), (1, 3), (1, 4), (2, 5), (2, 6), (3, 5), (6, 2)]
+            )
+        );
+        drop(db);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn ranks_sample_of_large_subgraph_levels() {
+        // Page 12 has the most links, but only pages 10, 15, 20 and 25 are ranked for the single
+        // remaining node.
+        let mut links: Vec<_> = (10..30).map(|page| (1, page)).collect();
+        links.extend([(12, 2), (12, 3), (12, 4), (20, 2), (20, 3)]);
+        let (dir, db) = import_graph("sample", links);
+
+        let subgraph = db
+            .get_subgraph(
+                &[1],
+                LinkDirection::Outgoing,
+                1,
+                2,
+                &SearchLimits::default(),
+            )
+            .unwrap();
+        let nodes: Vec<_> = subgraph.nodes.iter().map(|node| node.id).collect();
+        assert_eq!(nodes, vec![1, 20]);
+        assert_eq!(subgraph.count, 21);
+        drop(subgraph);
+        drop(db);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn finds_same_paths_as_brute_force_with_sparse_page_ids() {
+        // A random graph of pages with both low ids and ids too sparse for a bitset.
+        let mut rng = StdRng::seed_from_u64(42);
+        let pages: Vec<PageId> = (0..200)
+            .map(|i| {
+                if i % 3 == 0 {
+                    3_000_000_000 + i * 7
+                } else {
+                    i + 1
+                }
+            })
+            .collect();
+        let links: Vec<_> = (0..600)
+            .map(|_| {
+                (
+                    pages[rng.random_range(0..pages.len())],
+                    pages[rng.random_range(0..pages.len())],
+                )
+            })
+            .filter(|(source, target)| source != target)
+            .collect();
+        let (dir, db) = import_graph("sparse", links.iter().copied());
+
+        for _ in 0..30 {
+            let source = pages[rng.random_range(0..pages.len())];
+            let target = pages[rng.random_range(0..pages.len())];
+            let paths = db
+                .get_shortest_paths(
+                    source,
+                    target,
+                    &PathConstraints::default(),
+                    &SearchLimits::default(),
+                )
+                .unwrap();
+            let expected = brute_force_paths(&links, source, target);
+            if source != target {
+                assert_eq!(paths.count, expected.len() as u64);
+                assert_eq!(list_all(paths), expected);
+            }
+        }
+        drop(db);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn expands_large_frontiers_in_parallel_with_same_results() {
+        // Frontiers of 1500 pages in both directions, so that each search expands at least one
+        // level in parallel, with links that lead back or sideways mixed in.
+        let first = |i: u32| 1000 + i;
+        let second = |i: u32| 5000 + i;
+        let mut links = Vec::new();
+        for i in 0..1500 {
+            links.push((1, first(i)));
+            links.push((first(i), second(i * 7 % 1500)));
+            links.push((first(i), second((i * 13 + 5) % 1500)));
+            links.push((second(i), 2));
+            links.push((second(i), first(i)));
+            if i % 10 == 0 {
+                links.push((first(i), first((i + 1) % 1500)));
+                links.push((second(i), 3));
+            }
+        }
+        links.push((3, 2));
+        let (dir, db) = import_graph("parallel", links.iter().copied());
+
+        for (source, target) in [(1, 2), (1, 3), (second(0), second(1))] {
+            let expected = brute_force_paths(&links, source, target);
+            assert!(!expected.is_empty());
+            for _ in 0..5 {
+                let paths = db
+                    .get_shortest_paths(
+                        source,
+                        target,
+                        &PathConstraints::default(),
+                        &SearchLimits::default(),
+                    )
+                    .unwrap();
+                assert_eq!(paths.count, expected.len() as u64);
+                assert_eq!(list_all(paths), expected);
+            }
+        }
+        drop(db);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -1,78 +1,247 @@
-use crate::database::{Database, Metadata, Mode, PageId};
-use anyhow::Result;
+use crate::{
+    database::{Database, Metadata, Mode, PageId},
+    search::{
+        Cancellation, LinkDirection, PathConstraints, PathSelection, Paths, SearchError,
+        SearchLimits,
+    },
+};
+use anyhow::{Context, Result};
+use arc_swap::ArcSwap;
 use axum::{
     extract::{Extension, Query},
+    handler::Handler,
     http::{header::CACHE_CONTROL, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
-    routing::get,
+    routing::{get, MethodRouter},
     Json, Router,
 };
 use notify_debouncer_full::{new_debouncer, notify::RecursiveMode, DebounceEventResult};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     fs::{self},
-    path::Path,
-    sync::{Arc, RwLock},
-    time::Duration,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
 };
 use tokio::net::TcpListener;
 use tower::ServiceBuilder;
 use tower_http::{services::ServeDir, set_header::SetResponseHeaderLayer, timeout::TimeoutLayer};
 
+/// Options controlling which databases are served.
+#[derive(Debug, Clone)]
+pub struct ServeOptions {
+    /// Number of most recent dates to serve for each language.
+    pub keep_dates: usize,
+    /// Period during which older dates are still served after a newer database appears.
+    pub grace_period: Option<Duration>,
+    /// Maximum number of pages a single search may visit.
+    pub max_visited: Option<usize>,
+    /// Maximum number of links a single search may fetch from the database.
+    pub max_fetched: Option<usize>,
+}
+
+/// Interval at which to check whether the grace period of any older databases has expired, or
+/// whether a replaced database that could not be reopened yet should be retried.
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_mins(1);
+
+/// Maximum duration of a shortest paths search.
+const SEARCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Number of paths listed by default, and at most, in a single response.
+const DEFAULT_PATHS_LIMIT: usize = 10;
+const MAX_PATHS_LIMIT: usize = 1000;
+
+/// Maximum number of links by which listed paths may be longer than the shortest paths.
+const MAX_EXTRA_LENGTH: u32 = 3;
+
+/// Number of pages listed by default, and at most, for each distance in a neighborhood, and the
+/// maximum distance of a neighborhood.
+const DEFAULT_NEIGHBORHOOD_LIMIT: usize = 10;
+const MAX_NEIGHBORHOOD_LIMIT: usize = 1000;
+const MAX_NEIGHBORHOOD_DEPTH: u32 = 6;
+
+/// Number of nodes in a subgraph by default, and at most, and the maximum radius of a subgraph.
+const DEFAULT_SUBGRAPH_NODES: usize = 100;
+const MAX_SUBGRAPH_NODES: usize = 1000;
+const MAX_SUBGRAPH_RADIUS: u32 = 2;
+
+/// Maximum number of pages paths can be required to go via, as each is searched separately.
+const MAX_VIA_PAGES: usize = 5;
+
+/// Subdirectory of the databases directory through which replaced databases are reopened.
+const REOPEN_DIR: &str = ".reopen";
+
+/// A database that is opened for serving, along with the size and modification time of its file,
+/// to detect whether the file was replaced.
+#[derive(Debug)]
+struct OpenedDatabase {
+    db: Arc<Database>,
+    file: (u64, SystemTime),
+}
+
+fn file_identity(path: &Path) -> Result<(u64, SystemTime)> {
+    let metadata = fs::metadata(path)?;
+    Ok((metadata.len(), metadata.modified()?))
+}
+
+/// Open a database whose file was replaced in place. An environment can only be opened once per
+/// path, so while the previous instance still has the path open, the new file is opened through a
+/// hard link instead. The link is removed right away, as the open environment keeps the file alive.
+fn reopen(path: &Path) -> Result<Database> {
+    match Database::open(path, Mode::Serve) {
+        Err(e) if matches!(e.downcast_ref(), Some(heed::Error::EnvAlreadyOpened)) => {}
+        result => return result,
+    }
+
+    let file_name = path
+        .file_name()
+        .context("database path has no valid filename")?;
+    let link_dir = path.with_file_name(REOPEN_DIR);
+    fs::create_dir_all(&link_dir)?;
+    let link_path = link_dir.join(file_name);
+    let mut lock_path = link_path.clone().into_os_string();
+    lock_path.push("-lock");
+
+    fs::hard_link(path, &link_path)?;
+    let result = Database::open(&link_path, Mode::Serve);
+    fs::remove_file(&link_path)?;
+    if let Err(e) = fs::remove_file(&lock_path) {
+        log::debug!("failed to remove lock file of reopened database: {e}");
+    }
+    result
+}
+
 #[derive(Debug)]
 struct DatabaseSet {
-    inner: HashMap<Metadata, Database>,
+    inner: HashMap<Metadata, OpenedDatabase>,
     json: Json<Vec<Metadata>>,
+    expires_at: Option<SystemTime>, // when the first older database kept for a grace period expires
+    reopen_pending: bool, // whether a replaced database is still served from its previous file
 }
 
 impl DatabaseSet {
-    fn load(databases_dir: &Path) -> Result<Self> {
-        let mut inner: HashMap<Metadata, Database> = HashMap::new();
+    /// Load the databases in the given directory. Databases that are unchanged compared to the
+    /// previous set are reused instead of reopened. Databases whose file was replaced in place
+    /// keep being served from the previous file until the new file could be opened.
+    fn load(databases_dir: &Path, options: &ServeOptions, previous: &DatabaseSet) -> Result<Self> {
+        let mut inner: HashMap<Metadata, OpenedDatabase> = HashMap::new();
+        let mut expires_at: Option<SystemTime> = None;
+        let mut reopen_pending = false;
 
-        // Load all databases from the given directory.
+        // Find all databases in the given directory, grouped by language.
+        let mut candidates: HashMap<String, Vec<(Metadata, PathBuf)>> = HashMap::new();
         for entry in fs::read_dir(databases_dir)? {
             let path = entry?.path();
 
             match Database::get_metadata(&path) {
-                Ok(md) => match Database::open(&path, Mode::Serve) {
-                    Ok(db) => {
-                        // If any older databases were opened, close them again.
-                        while let Some(md2) = inner.keys().find(|&m| m.is_older(&md)) {
-                            log::info!("closing older database '{}'", md2.to_name());
-                            let md2 = md2.clone();
-                            inner.remove(&md2);
-                        }
+                Ok(md) => candidates
+                    .entry(md.language_code.clone())
+                    .or_default()
+                    .push((md, path)),
+                Err(e) => {
+                    log::debug!("skipping non-database path '{}': {}", path.display(), e);
+                }
+            }
+        }
 
-                        // Check if there are no newer databases.
-                        let newest = !inner.keys().any(|m| m.is_newer(&md));
+        let now = SystemTime::now();
+        for mut candidates in candidates.into_values() {
+            // Open the most recent databases first, skipping any that fail to open.
+            candidates.sort_by(|(a, _), (b, _)| b.date_code.cmp(&a.date_code));
+            let mut opened_at = Vec::new(); // modification times of the opened databases
+            for (md, path) in candidates {
+                let grace_expiry = opened_at
+                    .len()
+                    .checked_sub(options.keep_dates)
+                    .and_then(|i| Some(opened_at[i] + options.grace_period?));
+                if opened_at.len() >= options.keep_dates
+                    && grace_expiry.is_none_or(|expiry| expiry <= now)
+                {
+                    log::info!("skipping older database '{}'", md.to_name());
+                    continue;
+                }
 
-                        if newest {
-                            log::info!("opened database '{}'", md.to_name());
-                            inner.insert(md, db);
-                        } else {
-                            log::info!("skipping older database '{}'", md.to_name());
-                        }
-                    }
+                let file = match file_identity(&path) {
+                    Ok(file) => file,
                     Err(e) => {
                         log::warn!("skipping database '{}': {}", md.to_name(), e);
+                        continue;
                     }
-                },
-                Err(e) => {
-                    log::debug!("skipping non-database path '{}': {}", path.display(), e);
+                };
+
+                let (db, file) = match previous.inner.get(&md) {
+                    Some(opened) if opened.file == file => {
+                        log::debug!("reusing database '{}'", md.to_name());
+                        (opened.db.clone(), file)
+                    }
+                    Some(opened) => match reopen(&path) {
+                        Ok(db) => {
+                            log::info!("reopened replaced database '{}'", md.to_name());
+                            (Arc::new(db), file)
+                        }
+                        Err(e) => {
+                            // Keep the previous file identity, so reopening is retried later.
+                            log::warn!(
+                                "failed to reopen replaced database '{}', serving previous file: {}",
+                                md.to_name(),
+                                e
+                            );
+                            reopen_pending = true;
+                            (opened.db.clone(), opened.file)
+                        }
+                    },
+                    // Opening validates the format version and tables of the database, so files that
+                    // are invalid or incomplete are skipped instead of adopted.
+                    None => match Database::open(&path, Mode::Serve) {
+                        Ok(db) => {
+                            if grace_expiry.is_some() {
+                                log::info!(
+                                    "opened older database '{}' for grace period",
+                                    md.to_name()
+                                );
+                            } else {
+                                log::info!("opened database '{}'", md.to_name());
+                            }
+                            (Arc::new(db), file)
+                        }
+                        Err(e) => {
+                            log::warn!("skipping database '{}': {}", md.to_name(), e);
+                            continue;
+                        }
+                    },
+                };
+
+                if let Some(expiry) = grace_expiry {
+                    expires_at = Some(expires_at.map_or(expiry, |e| e.min(expiry)));
                 }
+                opened_at.push(file.1);
+                inner.insert(md, OpenedDatabase { db, file });
+            }
+        }
+
+        for md in previous.inner.keys() {
+            if !inner.contains_key(md) {
+                log::info!("closing database '{}'", md.to_name());
             }
         }
 
         log::info!("finished loading databases");
         let json = Self::to_json_internal(&inner);
-        Ok(Self { inner, json })
+        Ok(Self {
+            inner,
+            json,
+            expires_at,
+            reopen_pending,
+        })
     }
 
     fn empty() -> Self {
         Self {
             inner: HashMap::new(),
             json: Json(Vec::new()),
+            expires_at: None,
+            reopen_pending: false,
         }
     }
 
@@ -81,105 +250,488 @@ impl DatabaseSet {
         self.json.clone()
     }
 
-    /// Get a database by its metadata.
-    fn get_by_metadata(&self, metadata: &Metadata) -> Option<&Database> {
-        self.inner.get(metadata)
+    /// Get a database by its language code and date code, or the most recent database of the
+    /// language if no date code is given.
+    fn get(&self, language_code: &str, date_code: Option<&str>) -> Option<&Database> {
+        match date_code {
+            Some(date_code) => self
+                .inner
+                .get(&Metadata {
+                    language_code: language_code.to_string(),
+                    date_code: date_code.to_string(),
+                })
+                .map(|opened| opened.db.as_ref()),
+            None => self
+                .inner
+                .iter()
+                .filter(|(md, _)| md.language_code == language_code)
+                .max_by(|(a, _), (b, _)| a.date_code.cmp(&b.date_code))
+                .map(|(_, opened)| opened.db.as_ref()),
+        }
     }
 
-    /// Convert the inner hashmap to a list of metadata as JSON response sorted by language code.
-    fn to_json_internal(inner: &HashMap<Metadata, Database>) -> Json<Vec<Metadata>> {
-        let mut list = inner
-            .values()
-            .map(|db| db.metadata.clone())
-            .collect::<Vec<_>>();
+    /// Convert the inner hashmap to a list of metadata as JSON response sorted by language code,
+    /// and then from newest to oldest date.
+    fn to_json_internal(inner: &HashMap<Metadata, OpenedDatabase>) -> Json<Vec<Metadata>> {
+        let mut list = inner.keys().cloned().collect::<Vec<_>>();
 
-        // Sort alphabetically by language code.
-        list.sort_by(|a, b| a.language_code.cmp(&b.language_code));
+        // Sort alphabetically by language code, and then by descending date code.
+        list.sort_by(|a, b| {
+            a.language_code
+                .cmp(&b.language_code)
+                .then_with(|| b.date_code.cmp(&a.date_code))
+        });
 
         Json(list)
     }
 }
 
-async fn list_databases_handler(
-    Extension(databases): Extension<Arc<RwLock<DatabaseSet>>>,
-) -> Response {
-    databases.read().unwrap().to_json().into_response()
+/// The set of served databases, which is swapped out atomically when it is reloaded, so queries
+/// are never blocked by a reload.
+struct Databases {
+    current: ArcSwap<DatabaseSet>,
+    dir: PathBuf,
+    options: ServeOptions,
+    reload_lock: Mutex<()>, // prevents concurrent reloads from the watcher and grace period timer
+}
+
+impl Databases {
+    fn load(databases_dir: &Path, options: ServeOptions) -> Result<Self> {
+        let set = DatabaseSet::load(databases_dir, &options, &DatabaseSet::empty())?;
+        Ok(Self {
+            current: ArcSwap::from_pointee(set),
+            dir: databases_dir.to_path_buf(),
+            options,
+            reload_lock: Mutex::new(()),
+        })
+    }
+
+    /// Reload the databases. The new set is loaded next to the current one, which is only
+    /// replaced if loading succeeds.
+    fn reload(&self) {
+        let _guard = self.reload_lock.lock().unwrap();
+
+        let current = self.current.load_full();
+        match DatabaseSet::load(&self.dir, &self.options, &current) {
+            Ok(new) => self.current.store(Arc::new(new)),
+            Err(e) => log::error!("failed to reload databases, keeping current databases: {e}"),
+        }
+    }
+}
+
+async fn list_databases_handler(Extension(databases): Extension<Arc<Databases>>) -> Response {
+    databases.current.load().to_json().into_response()
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 struct ShortestPathsQuery {
     language_code: String,
-    date_code: String,
+    date_code: Option<String>, // defaults to the most recent date
     source: PageId,
     target: PageId,
+    avoid: Option<String>, // comma-separated ids of pages the paths may not go through
+    max_in_degree: Option<usize>, // the paths may not go through pages with more incoming links
+    max_out_degree: Option<usize>, // the paths may not go through pages with more outgoing links
+    via: Option<String>,   // comma-separated ids of pages the paths have to go through in order
+    #[serde(default)]
+    mode: SearchMode,
 }
 
-async fn shortest_paths_handler(
-    Extension(databases): Extension<Arc<RwLock<DatabaseSet>>>,
-    query: Query<ShortestPathsQuery>,
-) -> Response {
-    let query = query.0;
+/// The directions in which links are followed by a shortest paths search.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum SearchMode {
+    #[default]
+    Directed, // from the source to the target, following links in their direction
+    Undirected, // following links in either direction
+    Both,       // both from the source to the target and from the target to the source
+}
 
-    let metadata = Metadata {
-        language_code: query.language_code,
-        date_code: query.date_code,
+/// The shortest paths from the source to the target (forward) and back (backward).
+#[derive(Debug, Serialize)]
+struct PathsBothWays<'a> {
+    forward: Paths<'a>,
+    backward: Paths<'a>,
+}
+
+/// Parse a comma-separated list of page ids, or describe why it is invalid.
+fn parse_pages(list: Option<&str>) -> Result<Vec<PageId>, String> {
+    list.filter(|list| !list.is_empty())
+        .map_or(Ok(Vec::new()), |list| {
+            list.split(',').map(str::parse).collect()
+        })
+        .map_err(|e| format!("invalid page id: {e}"))
+}
+
+/// Parse the constraints on the paths from the query parameters, or describe why they are
+/// invalid.
+fn path_constraints(
+    avoid: Option<&str>,
+    max_in_degree: Option<usize>,
+    max_out_degree: Option<usize>,
+    via: Option<&str>,
+) -> Result<PathConstraints, String> {
+    let constraints = PathConstraints {
+        avoid: parse_pages(avoid)?,
+        max_in_degree,
+        max_out_degree,
+        via: parse_pages(via)?,
+        undirected: false,
+    };
+    if constraints.via.len() > MAX_VIA_PAGES {
+        return Err(format!("paths can go via at most {MAX_VIA_PAGES} pages"));
+    }
+    Ok(constraints)
+}
+
+/// Cancels a search when dropped.
+struct CancelOnDrop(Cancellation);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+/// Run a search on a database in a blocking task, and respond with its result. The search is
+/// stopped once its deadline passes, or when the request is dropped (e.g. because the client
+/// disconnected), instead of letting it run to completion in the background.
+async fn search_response(
+    databases: Arc<Databases>,
+    language_code: String,
+    date_code: Option<String>,
+    description: String,
+    search: impl FnOnce(&Database, &SearchLimits) -> Result<Response> + Send + 'static,
+) -> Response {
+    let cancellation = Cancellation::with_deadline(Instant::now() + SEARCH_TIMEOUT);
+    let _guard = CancelOnDrop(cancellation.clone());
+    let limits = SearchLimits {
+        cancellation,
+        max_visited: databases.options.max_visited,
+        max_fetched: databases.options.max_fetched,
     };
 
     let result = tokio::task::spawn_blocking(move || -> Response {
-        let databases = databases.read().unwrap();
-        match databases.get_by_metadata(&metadata) {
+        let databases = databases.current.load_full();
+        match databases.get(&language_code, date_code.as_deref()) {
             None => StatusCode::NOT_FOUND.into_response(),
-            Some(db) => match db.get_shortest_paths(query.source, query.target) {
-                Ok(paths) => Json(paths).into_response(),
-                Err(e) => {
-                    log::error!("failed getting shortest paths: {e}");
-                    StatusCode::INTERNAL_SERVER_ERROR.into_response()
-                }
+            Some(db) => match search(db, &limits) {
+                Ok(response) => response,
+                Err(e) => match e.downcast_ref::<SearchError>() {
+                    Some(SearchError::TimedOut) => {
+                        log::warn!("{description} timed out");
+                        StatusCode::REQUEST_TIMEOUT.into_response()
+                    }
+                    Some(SearchError::Cancelled) => StatusCode::REQUEST_TIMEOUT.into_response(),
+                    None => {
+                        log::error!("failed {description}: {e}");
+                        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+                    }
+                },
             },
         }
     })
     .await;
 
     result.unwrap_or_else(|e| {
-        log::error!("getting shortest paths task join error: {e}");
+        log::error!("search task join error: {e}");
         StatusCode::INTERNAL_SERVER_ERROR.into_response()
     })
 }
 
-pub async fn serve(databases_dir: &Path, web_dir: &Path, listening_port: u16) -> Result<()> {
-    let databases = Arc::new(RwLock::new(DatabaseSet::load(databases_dir)?));
+async fn shortest_paths_handler(
+    Extension(databases): Extension<Arc<Databases>>,
+    query: Query<ShortestPathsQuery>,
+) -> Response {
+    let query = query.0;
+    let mut constraints = match path_constraints(
+        query.avoid.as_deref(),
+        query.max_in_degree,
+        query.max_out_degree,
+        query.via.as_deref(),
+    ) {
+        Ok(constraints) => constraints,
+        Err(message) => return (StatusCode::BAD_REQUEST, message).into_response(),
+    };
+    constraints.undirected = query.mode == SearchMode::Undirected;
+
+    search_response(
+        databases,
+        query.language_code,
+        query.date_code,
+        format!(
+            "shortest paths search from {} to {}",
+            query.source, query.target
+        ),
+        move |db, limits| {
+            let forward =
+                db.get_shortest_paths(query.source, query.target, &constraints, limits)?;
+            if query.mode != SearchMode::Both {
+                return Ok(Json(forward).into_response());
+            }
+
+            // The way back goes via the same pages, in reverse order.
+            constraints.via.reverse();
+            let backward =
+                db.get_shortest_paths(query.target, query.source, &constraints, limits)?;
+            Ok(Json(PathsBothWays { forward, backward }).into_response())
+        },
+    )
+    .await
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct PathsQuery {
+    language_code: String,
+    date_code: Option<String>, // defaults to the most recent date
+    source: PageId,
+    target: PageId,
+    #[serde(default)]
+    offset: u64,
+    limit: Option<usize>,  // defaults to `DEFAULT_PATHS_LIMIT`
+    sample: Option<usize>, // if set, a random sample of this size is listed instead of a range
+    seed: Option<u64>,     // seed of the random sample, random by default
+    #[serde(default)]
+    max_extra_length: u32, // also list paths up to this many links longer than the shortest
+    avoid: Option<String>, // same as for shortest paths
+    max_in_degree: Option<usize>,
+    max_out_degree: Option<usize>,
+    via: Option<String>,
+}
+
+async fn paths_handler(
+    Extension(databases): Extension<Arc<Databases>>,
+    query: Query<PathsQuery>,
+) -> Response {
+    let query = query.0;
+    if query.sample.or(query.limit).unwrap_or(0) > MAX_PATHS_LIMIT {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("at most {MAX_PATHS_LIMIT} paths can be listed at once"),
+        )
+            .into_response();
+    }
+    if query.max_extra_length > MAX_EXTRA_LENGTH {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("paths can be at most {MAX_EXTRA_LENGTH} links longer than the shortest"),
+        )
+            .into_response();
+    }
+    let constraints = match path_constraints(
+        query.avoid.as_deref(),
+        query.max_in_degree,
+        query.max_out_degree,
+        query.via.as_deref(),
+    ) {
+        Ok(constraints) => constraints,
+        Err(message) => return (StatusCode::BAD_REQUEST, message).into_response(),
+    };
+    if query.max_extra_length > 0 && !constraints.via.is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            "paths longer than the shortest cannot be required to go via other pages",
+        )
+            .into_response();
+    }
+
+    let selection = match query.sample {
+        Some(size) => PathSelection::Sample {
+            size,
+            seed: query.seed.unwrap_or_else(rand::random),
+        },
+        None => PathSelection::Range {
+            offset: query.offset,
+            limit: query.limit.unwrap_or(DEFAULT_PATHS_LIMIT),
+        },
+    };
+
+    search_response(
+        databases,
+        query.language_code,
+        query.date_code,
+        format!("listing paths from {} to {}", query.source, query.target),
+        move |db, limits| {
+            if query.max_extra_length > 0 {
+                let paths = db.get_near_shortest_paths(
+                    query.source,
+                    query.target,
+                    query.max_extra_length,
+                    &constraints,
+                    limits,
+                    selection,
+                )?;
+                Ok(Json(paths).into_response())
+            } else {
+                let paths =
+                    db.get_shortest_paths(query.source, query.target, &constraints, limits)?;
+                Ok(Json(paths.into_list(selection)).into_response())
+            }
+        },
+    )
+    .await
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct NeighborhoodQuery {
+    language_code: String,
+    date_code: Option<String>, // defaults to the most recent date
+    source: PageId,
+    max_depth: u32,
+    direction: Option<LinkDirection>, // `out`, `in` or `both`, defaults to `out`
+    limit: Option<usize>,             // defaults to `DEFAULT_NEIGHBORHOOD_LIMIT`
+}
+
+async fn neighborhood_handler(
+    Extension(databases): Extension<Arc<Databases>>,
+    query: Query<NeighborhoodQuery>,
+) -> Response {
+    let query = query.0;
+    if query.max_depth > MAX_NEIGHBORHOOD_DEPTH {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("the maximum depth can be at most {MAX_NEIGHBORHOOD_DEPTH}"),
+        )
+            .into_response();
+    }
+    let limit = query.limit.unwrap_or(DEFAULT_NEIGHBORHOOD_LIMIT);
+    if limit > MAX_NEIGHBORHOOD_LIMIT {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("at most {MAX_NEIGHBORHOOD_LIMIT} pages can be listed per distance"),
+        )
+            .into_response();
+    }
+
+    search_response(
+        databases,
+        query.language_code,
+        query.date_code,
+        format!("neighborhood search from {}", query.source),
+        move |db, limits| {
+            let neighborhood = db.get_neighborhood(
+                query.source,
+                query.direction.unwrap_or(LinkDirection::Outgoing),
+                query.max_depth,
+                limit,
+                limits,
+            )?;
+            Ok(Json(neighborhood).into_response())
+        },
+    )
+    .await
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct SubgraphQuery {
+    language_code: String,
+    date_code: Option<String>,        // defaults to the most recent date
+    pages: String,                    // comma-separated ids of the center pages
+    radius: Option<u32>,              // defaults to 1
+    max_nodes: Option<usize>,         // defaults to `DEFAULT_SUBGRAPH_NODES`
+    direction: Option<LinkDirection>, // `out`, `in` or `both`, defaults to `both`
+}
+
+async fn subgraph_handler(
+    Extension(databases): Extension<Arc<Databases>>,
+    query: Query<SubgraphQuery>,
+) -> Response {
+    let query = query.0;
+    let radius = query.radius.unwrap_or(1);
+    let max_nodes = query.max_nodes.unwrap_or(DEFAULT_SUBGRAPH_NODES);
+    let centers = match parse_pages(Some(&query.pages)) {
+        Ok(centers) => centers,
+        Err(message) => return (StatusCode::BAD_REQUEST, message).into_response(),
+    };
+    if centers.is_empty() {
+        return (StatusCode::BAD_REQUEST, "at least one page is needed").into_response();
+    }
+    if radius > MAX_SUBGRAPH_RADIUS {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("the radius can be at most {MAX_SUBGRAPH_RADIUS}"),
+        )
+            .into_response();
+    }
+    if max_nodes > MAX_SUBGRAPH_NODES {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("subgraphs can have at most {MAX_SUBGRAPH_NODES} nodes"),
+        )
+            .into_response();
+    }
+    if centers.len() > max_nodes {
+        return (
+            StatusCode::BAD_REQUEST,
+            "all pages have to be nodes of the subgraph",
+        )
+            .into_response();
+    }
+
+    search_response(
+        databases,
+        query.language_code,
+        query.date_code,
+        format!("subgraph search around {}", query.pages),
+        move |db, limits| {
+            let subgraph = db.get_subgraph(
+                &centers,
+                query.direction.unwrap_or(LinkDirection::Both),
+                radius,
+                max_nodes,
+                limits,
+            )?;
+            Ok(Json(subgraph).into_response())
+        },
+    )
+    .await
+}
+
+/// Route a search handler, giving it access to the databases. The timeout is a backstop in case
+/// the search does not stop by its deadline.
+fn search_route<H, T>(handler: H, databases: &Arc<Databases>) -> MethodRouter
+where
+    H: Handler<T, ()>,
+    T: 'static,
+{
+    get(handler).layer(
+        ServiceBuilder::new()
+            .layer(TimeoutLayer::with_status_code(
+                StatusCode::REQUEST_TIMEOUT,
+                SEARCH_TIMEOUT + Duration::from_secs(1),
+            ))
+            .layer(Extension(databases.clone())),
+    )
+}
+
+pub async fn serve(
+    databases_dir: &Path,
+    web_dir: &Path,
+    listening_port: u16,
+    options: ServeOptions,
+) -> Result<()> {
+    let databases = Arc::new(Databases::load(databases_dir, options)?);
 
     let databases_clone = databases.clone();
-    let databases_dir_clone = databases_dir.to_path_buf();
     let mut debouncer = new_debouncer(
         Duration::from_secs(5),
         None,
         move |res: DebounceEventResult| match res {
             Ok(events) => {
                 // Check if any of the events involve a change in a database.
-                let reload = events.into_iter().any(|e| {
+                let reload_needed = events.into_iter().any(|e| {
                     let is_change = e.kind.is_create() || e.kind.is_modify() || e.kind.is_remove();
                     let involves_db = e.paths.iter().any(|p| Database::get_metadata(p).is_ok());
                     is_change && involves_db
                 });
 
-                if reload {
+                if reload_needed {
                     log::info!("detected changes in databases directory, reloading");
-                    let mut guard = databases_clone.write().unwrap();
-
-                    // Replace current with empty to drop currently opened databases.
-                    *guard = DatabaseSet::empty();
-
-                    // Load new databases and replace the empty one again.
-                    match DatabaseSet::load(&databases_dir_clone) {
-                        Ok(new) => {
-                            *guard = new;
-                        }
-                        Err(e) => {
-                            log::error!("failed to reload databases: {e}");
-                        }
-                    }
+                    databases_clone.reload();
                 }
             }
             Err(e) => {
@@ -188,6 +740,31 @@ pub async fn serve(databases_dir: &Path, web_dir: &Path, listening_port: u16) ->
         },
     )?;
 
+    // Periodically check whether older databases kept for a grace period should be closed, and
+    // whether replaced databases that could not be reopened yet should be retried.
+    let databases_clone = databases.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(RELOAD_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            let current = databases_clone.current.load();
+            let expired = current
+                .expires_at
+                .is_some_and(|expiry| expiry <= SystemTime::now());
+            let reopen_pending = current.reopen_pending;
+            drop(current);
+            if expired {
+                log::info!("grace period of older database expired, reloading");
+            } else if reopen_pending {
+                log::info!("retrying to reopen replaced databases, reloading");
+            } else {
+                continue;
+            }
+            let databases = databases_clone.clone();
+            let _ = tokio::task::spawn_blocking(move || databases.reload()).await;
+        }
+    });
+
     // Watch for changes in the databases directory.
     debouncer.watch(databases_dir, RecursiveMode::NonRecursive)?;
 
@@ -198,15 +775,14 @@ pub async fn serve(databases_dir: &Path, web_dir: &Path, listening_port: u16) ->
         )
         .route(
             "/api/shortest_paths",
-            get(shortest_paths_handler).layer(
-                ServiceBuilder::new()
-                    .layer(TimeoutLayer::with_status_code(
-                        StatusCode::REQUEST_TIMEOUT,
-                        Duration::from_secs(10),
-                    )) // timeout to prevent long-running searches
-                    .layer(Extension(databases.clone())), // give access to the databases
-            ),
+            search_route(shortest_paths_handler, &databases),
+        )
+        .route("/api/paths", search_route(paths_handler, &databases))
+        .route(
+            "/api/neighborhood",
+            search_route(neighborhood_handler, &databases),
         )
+        .route("/api/subgraph", search_route(subgraph_handler, &databases))
         .nest_service(
             "/assets", // treat frontend "assets" files separately, since they have hashed filenames
             ServiceBuilder::new()
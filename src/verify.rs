@@ -0,0 +1,285 @@
+use crate::{
+    database::{Database, PageId},
+    import::{MergedRuns, SortedRuns, RUN_SIZE_EDGES},
+};
+use anyhow::Result;
+use heed::RoTxn;
+use std::{cmp::Ordering, collections::BTreeMap, fmt, fs, path::Path};
+
+/// Maximum number of examples logged for each kind of violation.
+const MAX_EXAMPLES: u64 = 10;
+
+/// The kinds of integrity violations that can be found in a database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Violation {
+    /// An adjacency list in the incoming table is not sorted and deduplicated.
+    UnsortedIncoming,
+    /// An adjacency list in the outgoing table is not sorted and deduplicated.
+    UnsortedOutgoing,
+    /// A link in the incoming table is missing from the outgoing table.
+    MissingOutgoing,
+    /// A link in the outgoing table is missing from the incoming table.
+    MissingIncoming,
+    /// A redirect source has incoming or outgoing links.
+    LinkedRedirect,
+    /// A redirect target is itself a redirect.
+    RedirectChain,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Violation::UnsortedIncoming => "unsorted or duplicated incoming links",
+            Violation::UnsortedOutgoing => "unsorted or duplicated outgoing links",
+            Violation::MissingOutgoing => "incoming links missing from outgoing table",
+            Violation::MissingIncoming => "outgoing links missing from incoming table",
+            Violation::LinkedRedirect => "redirects with links",
+            Violation::RedirectChain => "redirects to redirects",
+        })
+    }
+}
+
+/// The result of verifying a database, with the number of violations of each kind.
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    pub violations: BTreeMap<Violation, u64>,
+}
+
+impl VerifyReport {
+    fn add(&mut self, violation: Violation, details: impl FnOnce() -> String) {
+        let count = self.violations.entry(violation).or_default();
+        *count += 1;
+        if *count <= MAX_EXAMPLES {
+            log::warn!("{violation}: {}", details());
+        }
+    }
+
+    /// The total number of violations found.
+    pub fn total(&self) -> u64 {
+        self.violations.values().sum()
+    }
+}
+
+/// An order-independent fingerprint of a set of links, used to compare the incoming and outgoing
+/// tables without holding either of them in memory.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct LinkFingerprint {
+    count: u64,
+    sums: [u64; 2],
+}
+
+impl LinkFingerprint {
+    fn add(&mut self, source: PageId, target: PageId) {
+        let link = (u64::from(source) << 32) | u64::from(target);
+        self.count += 1;
+        self.sums[0] = self.sums[0].wrapping_add(mix(link ^ 0x243f_6a88_85a3_08d3));
+        self.sums[1] = self.sums[1].wrapping_add(mix(link ^ 0x1319_8a2e_0370_7344));
+    }
+}
+
+/// The `SplitMix64` finalizer, to spread link bits over the whole fingerprint.
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+fn is_sorted_and_deduplicated(pages: &[PageId]) -> bool {
+    pages.windows(2).all(|w| w[0] < w[1])
+}
+
+impl Database {
+    /// Verify the integrity of the database: adjacency lists must be sorted and deduplicated, the
+    /// incoming and outgoing tables must be exact transposes of each other, redirect sources must
+    /// not have links, and redirect targets must not be redirects themselves. If the link tables
+    /// differ, the differences are located using sorted runs of links written to the temporary
+    /// path, which should point to a directory that does not yet exist.
+    pub fn verify(&self, tmp_path: &Path) -> Result<VerifyReport> {
+        let mut report = VerifyReport::default();
+        let txn = self.read_txn()?;
+
+        log::info!("checking incoming table");
+        let mut incoming_fingerprint = LinkFingerprint::default();
+        for entry in self.iter_incoming(&txn)? {
+            let (target, sources) = entry?;
+            if !is_sorted_and_deduplicated(&sources) {
+                report.add(Violation::UnsortedIncoming, || format!("page {target}"));
+            }
+            for source in sources {
+                incoming_fingerprint.add(source, target);
+            }
+        }
+
+        log::info!("checking outgoing table");
+        let mut outgoing_fingerprint = LinkFingerprint::default();
+        for entry in self.iter_outgoing(&txn)? {
+            let (source, targets) = entry?;
+            if !is_sorted_and_deduplicated(&targets) {
+                report.add(Violation::UnsortedOutgoing, || format!("page {source}"));
+            }
+            for target in targets {
+                outgoing_fingerprint.add(source, target);
+            }
+        }
+
+        if incoming_fingerprint != outgoing_fingerprint {
+            log::warn!(
+                "incoming ({} links) and outgoing ({} links) tables differ, locating differences",
+                incoming_fingerprint.count,
+                outgoing_fingerprint.count
+            );
+            self.locate_missing_links(&txn, &mut report, tmp_path, RUN_SIZE_EDGES)?;
+        }
+
+        log::info!("checking redirects table");
+        for entry in self.iter_redirects(&txn)? {
+            let (source, target) = entry?;
+            if !self.get_incoming_links(&txn, source)?.is_empty()
+                || !self.get_outgoing_links(&txn, source)?.is_empty()
+            {
+                report.add(Violation::LinkedRedirect, || format!("page {source}"));
+            }
+            if let Some(next) = self.get_redirect(&txn, target)? {
+                report.add(Violation::RedirectChain, || {
+                    format!("{source} -> {target} -> {next}")
+                });
+            }
+        }
+
+        txn.commit()?;
+        Ok(report)
+    }
+
+    /// Find the links that are present in only one of the incoming and outgoing tables. Both
+    /// tables are sorted externally into (source, target) pairs and then merged, so this takes a
+    /// pass over each table and disk space for all links. It is only done once the fingerprints
+    /// show that the tables differ.
+    fn locate_missing_links(
+        &self,
+        txn: &RoTxn<'_>,
+        report: &mut VerifyReport,
+        tmp_path: &Path,
+        run_size: usize,
+    ) -> Result<()> {
+        log::info!("sorting incoming links into runs");
+        let incoming_dir = tmp_path.join("incoming");
+        fs::create_dir_all(&incoming_dir)?;
+        let mut incoming_runs = SortedRuns::default();
+        for entry in self.iter_incoming(txn)? {
+            let (target, sources) = entry?;
+            for source in sources {
+                incoming_runs.push((source, target), &incoming_dir, run_size)?;
+            }
+        }
+        incoming_runs.write_run(&incoming_dir)?;
+
+        log::info!("sorting outgoing links into runs");
+        let outgoing_dir = tmp_path.join("outgoing");
+        fs::create_dir_all(&outgoing_dir)?;
+        let mut outgoing_runs = SortedRuns::default();
+        for entry in self.iter_outgoing(txn)? {
+            let (source, targets) = entry?;
+            for target in targets {
+                outgoing_runs.push((source, target), &outgoing_dir, run_size)?;
+            }
+        }
+        outgoing_runs.write_run(&outgoing_dir)?;
+
+        log::info!("comparing sorted links");
+        let mut incoming = MergedRuns::open(&incoming_runs.paths)?;
+        let mut outgoing = MergedRuns::open(&outgoing_runs.paths)?;
+        let mut next_incoming = incoming.next_edge()?;
+        let mut next_outgoing = outgoing.next_edge()?;
+        loop {
+            let order = match (next_incoming, next_outgoing) {
+                (None, None) => break,
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(a), Some(b)) => a.cmp(&b),
+            };
+            if order == Ordering::Less {
+                let (source, target) = next_incoming.unwrap();
+                report.add(Violation::MissingOutgoing, || {
+                    format!("{source} -> {target}")
+                });
+            }
+            if order == Ordering::Greater {
+                let (source, target) = next_outgoing.unwrap();
+                report.add(Violation::MissingIncoming, || {
+                    format!("{source} -> {target}")
+                });
+            }
+            if order != Ordering::Greater {
+                next_incoming = incoming.next_edge()?;
+            }
+            if order != Ordering::Less {
+                next_outgoing = outgoing.next_edge()?;
+            }
+        }
+
+        fs::remove_dir_all(tmp_path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Mode;
+
+    #[test]
+    fn fingerprint_is_order_independent() {
+        let mut a = LinkFingerprint::default();
+        a.add(1, 2);
+        a.add(3, 4);
+        let mut b = LinkFingerprint::default();
+        b.add(3, 4);
+        b.add(1, 2);
+        assert_eq!(a, b);
+
+        let mut c = LinkFingerprint::default();
+        c.add(2, 1);
+        c.add(3, 4);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn detects_unsorted_and_duplicated_lists() {
+        assert!(is_sorted_and_deduplicated(&[]));
+        assert!(is_sorted_and_deduplicated(&[1, 2, 5]));
+        assert!(!is_sorted_and_deduplicated(&[1, 1, 2]));
+        assert!(!is_sorted_and_deduplicated(&[2, 1]));
+    }
+
+    #[test]
+    fn locates_links_missing_from_outgoing_table() {
+        let dir = std::env::temp_dir().join(format!("wikipath-verify-{}", std::process::id()));
+        let db_path = dir.join("wp-verify-20260101");
+        fs::create_dir_all(&db_path).unwrap();
+
+        // Links added to the incoming table after generating the outgoing table are missing from it.
+        let db = Database::open(&db_path, Mode::Build).unwrap();
+        let mut txn = db.write_txn().unwrap();
+        db.insert_links_incoming(&mut txn, 2, vec![1, 3]).unwrap();
+        db.insert_links_incoming(&mut txn, 3, vec![1]).unwrap();
+        db.generate_outgoing_table(&mut txn).unwrap();
+        db.insert_links_incoming(&mut txn, 2, vec![4]).unwrap();
+        db.insert_links_incoming(&mut txn, 4, vec![1, 3]).unwrap();
+        txn.commit().unwrap();
+
+        let mut report = VerifyReport::default();
+        let txn = db.read_txn().unwrap();
+        let tmp_path = dir.join("verify");
+        db.locate_missing_links(&txn, &mut report, &tmp_path, 2)
+            .unwrap();
+        txn.commit().unwrap();
+        assert_eq!(
+            report.violations,
+            BTreeMap::from([(Violation::MissingOutgoing, 3)])
+        );
+        assert!(!tmp_path.exists());
+        drop(db);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
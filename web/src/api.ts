@@ -5,7 +5,7 @@ import {
   DatabaseSchema,
   type Page,
   type Paths,
-  PathsSchema,
+  PathListSchema,
   WikipediaRandomSchema,
   WikipediaSearchSchema,
   WikipediaTitlesSchema,
@@ -19,6 +19,9 @@ const headers = {
   "Api-User-Agent": `Wikipath/${import.meta.env.VERSION} (https://github.com/ldobbelsteen/wikipath/)`,
 };
 
+/** The maximum number of paths the server lists in a single response. */
+const maxPathsPerRequest = 1000;
+
 const get = async <T, U>(
   url: string,
   schema: z.Schema<T, U>,
@@ -67,19 +70,19 @@ export const fetchShortestPaths = async (
   targetId: number,
   maxPaths = 8,
 ): Promise<Paths> => {
-  const url = `/api/shortest_paths?language-code=${database.languageCode}&date-code=${database.dateCode}&source=${sourceId.toString()}&target=${targetId.toString()}`;
-  const result = await get(url, PathsSchema);
-  const pathsOfIds = extractFullPaths(result.source, result.links, maxPaths);
+  const sample = Math.min(maxPaths, maxPathsPerRequest);
+  const url = `/api/paths?language-code=${database.languageCode}&date-code=${database.dateCode}&source=${sourceId.toString()}&target=${targetId.toString()}&sample=${sample.toString()}`;
+  const result = await get(url, PathListSchema);
   const titles = await fetchTitles(
     result.languageCode,
-    flattenUnique(pathsOfIds),
+    flattenUnique(result.paths),
   );
   const idToPage = (id: number): Page => ({ id, title: titles[id] });
   return {
     ...result,
     source: idToPage(result.source),
     target: idToPage(result.target),
-    paths: pathsOfIds.map((path) => path.map(idToPage)),
+    paths: result.paths.map((path) => path.map(idToPage)),
   };
 };
 
@@ -141,31 +144,3 @@ export const fetchSuggestions = async (
   }
   return result;
 };
-
-function extractFullPaths(
-  source: number,
-  links: Record<number, number[]>,
-  maxPaths: number,
-): number[][] {
-  const result: number[][] = [];
-  const recurse = (current: number, currentPath: number[]): boolean => {
-    if (current in links && links[current].length > 0) {
-      const targets = links[current];
-      targets.sort();
-      for (const target of targets) {
-        const maxReached = recurse(target, [...currentPath, target]);
-        if (maxReached) {
-          return true;
-        }
-      }
-    } else {
-      result.push(currentPath);
-      if (result.length >= maxPaths) {
-        return true;
-      }
-    }
-    return false;
-  };
-  recurse(source, [source]);
-  return result;
-}
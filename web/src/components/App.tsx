@@ -64,15 +64,22 @@ const App = () => {
       <span>
         {paths === "loading" || paths === undefined
           ? "\u00A0"
-          : paths.count === 0
-            ? "No paths found"
-            : `Found ${paths.count.toString()} ${
-                paths.count === 1 ? "path" : "paths"
-              } of degree ${paths.length.toString()}.${
-                paths.count > paths.paths.length
-                  ? ` A random sample of ${paths.paths.length.toString()} paths is shown below.`
-                  : ""
-              }`}
+          : paths.incomplete !== undefined
+            ? `Search stopped early after visiting ${(
+                paths.incomplete.forwardVisited +
+                paths.incomplete.backwardVisited
+              ).toString()} pages, no paths of degree ${(
+                paths.incomplete.forwardDepth + paths.incomplete.backwardDepth
+              ).toString()} or less exist`
+            : paths.count === 0
+              ? "No paths found"
+              : `Found ${paths.countIsApproximate ? "more than " : ""}${paths.count.toString()} ${
+                  paths.count === 1 ? "path" : "paths"
+                } of degree ${paths.length.toString()}.${
+                  paths.count > paths.paths.length
+                    ? ` A random sample of ${paths.paths.length.toString()} paths is shown below.`
+                    : ""
+                }`}
       </span>
       {paths !== "loading" &&
         paths !== undefined &&
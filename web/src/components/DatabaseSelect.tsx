@@ -20,6 +20,10 @@ export const defaultDatabase = (databases: Database[]) => {
   toast.error("No languages available on this server");
 };
 
+/** Key identifying a database, since a language may be served at multiple dates. */
+const databaseKey = (database: Database) =>
+  `${database.languageCode}-${database.dateCode}`;
+
 export const DatabaseSelect = (props: {
   disabled: boolean;
   selected: Database | undefined;
@@ -53,17 +57,27 @@ export const DatabaseSelect = (props: {
       <Select
         label="Select Wikipedia language"
         disabled={props.disabled}
-        value={props.selected.languageCode}
+        value={databaseKey(props.selected)}
         onChange={(ev) => {
           setSelected(
             databases.find(
-              (database) => database.languageCode === ev.target.value,
+              (database) => databaseKey(database) === ev.target.value,
             ),
           );
         }}
         options={databases.map((database) => ({
-          value: database.languageCode,
-          children: <>{database.languageCode}</>,
+          value: databaseKey(database),
+          children: (
+            <>
+              {databases.some(
+                (other) =>
+                  other.languageCode === database.languageCode &&
+                  other.dateCode !== database.dateCode,
+              )
+                ? `${database.languageCode} (${database.dateCode})`
+                : database.languageCode}
+            </>
+          ),
         }))}
       />
     )
@@ -22,6 +22,20 @@ export interface Paths {
   paths: Page[][];
   length: number;
   count: number;
+  /** Whether there are more paths than the count, which is capped to stay exact. */
+  countIsApproximate: boolean;
+  incomplete?: SearchProgress;
+}
+
+/** How far a search got before it exceeded the server's search budget. */
+export interface SearchProgress {
+  forwardDepth: number;
+  backwardDepth: number;
+  forwardFrontier: number;
+  backwardFrontier: number;
+  forwardVisited: number;
+  backwardVisited: number;
+  fetched: number;
 }
 
 const IdSchema = z.number().int().nonnegative();
@@ -37,22 +51,28 @@ export const DatabaseSchema = z.object({
   dateCode: z.string().min(1),
 });
 
-export const PathsSchema = z.object({
+export const PathListSchema = z.object({
   source: IdSchema,
   sourceIsRedirect: z.boolean(),
   target: IdSchema,
   targetIsRedirect: z.boolean(),
-  links: z.record(
-    z
-      .string()
-      .min(1)
-      .transform((s) => Number.parseInt(s, 10)),
-    z.array(IdSchema),
-  ),
+  paths: z.array(z.array(IdSchema)),
   languageCode: z.string().min(1),
   dateCode: z.string().min(1),
   length: z.number().int().nonnegative(),
   count: z.number().int().nonnegative(),
+  countIsApproximate: z.boolean(),
+  incomplete: z
+    .object({
+      forwardDepth: z.number().int().nonnegative(),
+      backwardDepth: z.number().int().nonnegative(),
+      forwardFrontier: z.number().int().nonnegative(),
+      backwardFrontier: z.number().int().nonnegative(),
+      forwardVisited: z.number().int().nonnegative(),
+      backwardVisited: z.number().int().nonnegative(),
+      fetched: z.number().int().nonnegative(),
+    })
+    .optional(),
 });
 
 export const WikipediaRandomSchema = z